@@ -1,6 +1,11 @@
+use std::cmp;
+use std::env;
+use std::str::FromStr;
+
 use lazy_static::lazy_static;
 
-use crate::bigint::{BigInt, ONE, THREE, TWO};
+use crate::bigint::{mod_div, BigInt, ONE, THREE, TWO};
+use crate::error::Error;
 
 /// 巴雷特模乘，需要确保 x < mod_num^2
 pub fn barrett_mod(x: &BigInt, m: &BigInt, mod_num: &BigInt) -> BigInt {
@@ -17,7 +22,11 @@ pub fn barrett_mod(x: &BigInt, m: &BigInt, mod_num: &BigInt) -> BigInt {
     res
 }
 
+/// 基于巴雷特取模的模幂，b 为 0 时返回 1
 pub fn mod_power(a: &BigInt, b: &BigInt, barrett_m: &BigInt, mod_num: &BigInt) -> BigInt {
+    if b.is_zero() {
+        return ONE.clone();
+    }
     let mut res = BigInt::with_capacity(2);
     res.length = 1;
     res.value[0] = 1;
@@ -40,24 +49,280 @@ pub fn mod_power(a: &BigInt, b: &BigInt, barrett_m: &BigInt, mod_num: &BigInt) -
     res
 }
 
-/// 扩展模 `mod_num` 欧几里得算法，返回 `(gcd, u, v)`, `d = ua + vb`
-pub fn extended_euclid(
-    a: u64,
-    b: u64,
-    barrett_m: &BigInt,
-    mod_num: &BigInt,
-) -> (u64, BigInt, BigInt) {
-    if b != 0 {
-        let q = a / b;
-        let r = a % b;
-        let (d, mut u, v) = extended_euclid(b, r, barrett_m, mod_num);
-        let qv = barrett_mod(&(&v * q), barrett_m, mod_num);
-        if u < qv {
-            u = &u + &mod_num;
-        }
-        (d, v, barrett_mod(&(&u - &qv), barrett_m, mod_num))
+/// 蒙哥马利模乘上下文，R = 2^(VALUE_LEN * n.length)，要求模数为奇数
+#[derive(Clone, Debug)]
+pub struct MontgomeryContext {
+    n: BigInt,
+    /// -n^-1 mod 2^VALUE_LEN
+    n_prime: u64,
+    /// R mod n，即蒙哥马利形式下的 1
+    r: BigInt,
+    /// R^2 mod n，用于转入蒙哥马利形式
+    r2: BigInt,
+}
+
+impl MontgomeryContext {
+    /// 模幂的窗口宽度（bit），需要整除 `BigInt::VALUE_LEN`
+    const WINDOW: u64 = 4;
+
+    pub fn new(n: &BigInt) -> Option<Self> {
+        if n.value[0] & 1 == 0 || n == &*ONE {
+            return None;
+        }
+
+        // 牛顿迭代求 n[0]^-1 mod 2^64，每次迭代精确位数翻倍
+        let n0 = n.value[0];
+        let mut inv: u64 = 1;
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(n0.wrapping_mul(inv)));
+        }
+        let n_prime = inv.wrapping_neg();
+
+        let shift = BigInt::VALUE_LEN * n.length as u64;
+        let (_, r) = mod_div(&(&*ONE << shift), n);
+        let (_, r2) = mod_div(&(&*ONE << (2 * shift)), n);
+        Some(Self {
+            n: n.clone(),
+            n_prime,
+            r,
+            r2,
+        })
+    }
+
+    /// 蒙哥马利模乘 (CIOS)，返回 a * b * R^-1 mod n，要求 a, b < n
+    pub fn mont_mul(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        let s = self.n.length;
+        let n = &self.n.value;
+        let mut t = vec![0u64; s + 2];
+        let get_val = |x: &BigInt, i: usize| if i < x.length { x.value[i] } else { 0 };
+
+        for i in 0..s {
+            let ai = get_val(a, i) as u128;
+            let mut carry: u128 = 0;
+            for (j, tj) in t.iter_mut().enumerate().take(s) {
+                let sum = *tj as u128 + ai * get_val(b, j) as u128 + carry;
+                *tj = sum as u64;
+                carry = sum >> BigInt::VALUE_LEN;
+            }
+            let sum = t[s] as u128 + carry;
+            t[s] = sum as u64;
+            t[s + 1] = (sum >> BigInt::VALUE_LEN) as u64;
+
+            // 选取 m 使得 t + m * n 的最低 limb 为 0，然后整体右移一个 limb
+            let m = t[0].wrapping_mul(self.n_prime) as u128;
+            let mut carry = (t[0] as u128 + m * n[0] as u128) >> BigInt::VALUE_LEN;
+            for j in 1..s {
+                let sum = t[j] as u128 + m * n[j] as u128 + carry;
+                t[j - 1] = sum as u64;
+                carry = sum >> BigInt::VALUE_LEN;
+            }
+            let sum = t[s] as u128 + carry;
+            t[s - 1] = sum as u64;
+            t[s] = t[s + 1] + (sum >> BigInt::VALUE_LEN) as u64;
+            t[s + 1] = 0;
+        }
+
+        let mut res = BigInt::from_slice(&t[0..=s]);
+        res.remove_front_zeros();
+        if res >= self.n {
+            res = &res - &self.n;
+        }
+        res
+    }
+
+    pub fn to_mont(&self, a: &BigInt) -> BigInt {
+        self.mont_mul(&self.reduce(a), &self.r2)
+    }
+
+    pub fn to_normal(&self, a: &BigInt) -> BigInt {
+        self.mont_mul(a, &ONE)
+    }
+
+    /// a * b mod n
+    pub fn mod_mul(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        self.mont_mul(&self.to_mont(a), &self.reduce(b))
+    }
+
+    /// 固定窗口的模幂 a^b mod n
+    pub fn mod_power(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        let mask = (1 << Self::WINDOW) - 1;
+        let mut table = Vec::with_capacity(1 << Self::WINDOW);
+        table.push(self.r.clone());
+        table.push(self.to_mont(a));
+        for i in 2..=mask as usize {
+            let next = self.mont_mul(&table[i - 1], &table[1]);
+            table.push(next);
+        }
+
+        let mut res = self.r.clone();
+        let mut started = false;
+        for i in (0..b.length).rev() {
+            for k in (0..BigInt::VALUE_LEN / Self::WINDOW).rev() {
+                let window = (b.value[i] >> (k * Self::WINDOW)) & mask;
+                if started {
+                    for _ in 0..Self::WINDOW {
+                        res = self.mont_mul(&res, &res);
+                    }
+                }
+                if window != 0 {
+                    res = self.mont_mul(&res, &table[window as usize]);
+                    started = true;
+                }
+            }
+        }
+        self.to_normal(&res)
+    }
+
+    fn reduce(&self, a: &BigInt) -> BigInt {
+        if a < &self.n {
+            a.clone()
+        } else {
+            mod_div(a, &self.n).1
+        }
+    }
+}
+
+/// 模幂运算的后端
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ModPowBackend {
+    Barrett,
+    #[default]
+    Montgomery,
+}
+
+impl ModPowBackend {
+    /// 从环境变量 `RSA_MODPOW_BACKEND` 读取后端，缺省为蒙哥马利
+    pub fn from_env() -> Self {
+        lazy_static! {
+            static ref BACKEND: ModPowBackend = env::var("RSA_MODPOW_BACKEND")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default();
+        }
+        *BACKEND
+    }
+}
+
+impl FromStr for ModPowBackend {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "barrett" => Ok(Self::Barrett),
+            "montgomery" => Ok(Self::Montgomery),
+            _ => Err(Error::Unsupported("Unknown mod power backend")),
+        }
+    }
+}
+
+/// 针对固定模数预计算好的模幂上下文
+#[derive(Clone, Debug)]
+pub enum ModPowContext {
+    Barrett { barrett_m: BigInt, mod_num: BigInt },
+    Montgomery(MontgomeryContext),
+}
+
+impl ModPowContext {
+    /// 偶数模数无法使用蒙哥马利，此时退回巴雷特
+    pub fn new(mod_num: &BigInt, backend: ModPowBackend) -> Self {
+        if backend == ModPowBackend::Montgomery {
+            if let Some(ctx) = MontgomeryContext::new(mod_num) {
+                return Self::Montgomery(ctx);
+            }
+        }
+        Self::Barrett {
+            barrett_m: mod_num.barrett_m(),
+            mod_num: mod_num.clone(),
+        }
+    }
+
+    /// a * b mod n，要求 a, b < n
+    pub fn mod_mul(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        match self {
+            Self::Barrett { barrett_m, mod_num } => barrett_mod(&(a * b), barrett_m, mod_num),
+            Self::Montgomery(ctx) => ctx.mod_mul(a, b),
+        }
+    }
+
+    pub fn mod_power(&self, a: &BigInt, b: &BigInt) -> BigInt {
+        match self {
+            Self::Barrett { barrett_m, mod_num } => mod_power(a, b, barrett_m, mod_num),
+            Self::Montgomery(ctx) => ctx.mod_power(a, b),
+        }
+    }
+}
+
+/// 欧几里得算法求最大公约数
+pub fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while !b.is_zero() {
+        let (_, r) = mod_div(&a, &b);
+        (a, b) = (b, r);
+    }
+    a
+}
+
+/// Stein 二进制最大公约数，只用到移位和减法
+pub fn binary_gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    if a.is_zero() {
+        return b.clone();
+    }
+    if b.is_zero() {
+        return a.clone();
+    }
+
+    let (za, zb) = (a.trailing_zeros(), b.trailing_zeros());
+    let mut a = a >> za;
+    let mut b = b >> zb;
+    // a、b 均为奇数，相减后必为偶数
+    loop {
+        if a > b {
+            (a, b) = (b, a);
+        }
+        b = &b - &a;
+        if b.is_zero() {
+            return &a << cmp::min(za, zb);
+        }
+        b = &b >> b.trailing_zeros();
+    }
+}
+
+pub fn lcm(a: &BigInt, b: &BigInt) -> BigInt {
+    if a.is_zero() || b.is_zero() {
+        return BigInt::with_capacity(1);
+    }
+    &(a / &binary_gcd(a, b)) * b
+}
+
+/// 扩展欧几里得算法，返回 `(gcd, u)`，满足 `u * a ≡ gcd (mod m)` 且 `0 <= u < m`
+pub fn extended_euclid(a: &BigInt, m: &BigInt) -> (BigInt, BigInt) {
+    // 系数始终保持在模 m 意义下，避免出现负数
+    let (_, mut r) = mod_div(a, m);
+    let mut old_r = m.clone();
+    let mut u = ONE.clone();
+    let mut old_u = BigInt::with_capacity(1);
+    while !r.is_zero() {
+        let (q, next_r) = mod_div(&old_r, &r);
+        (old_r, r) = (r, next_r);
+
+        let (_, qu) = mod_div(&(&q * &u), m);
+        let next_u = if old_u >= qu {
+            &old_u - &qu
+        } else {
+            &(&old_u + m) - &qu
+        };
+        (old_u, u) = (u, next_u);
+    }
+    let (_, old_u) = mod_div(&old_u, m);
+    (old_r, old_u)
+}
+
+/// 模逆元 a^-1 mod m，a 与 m 不互素时返回 `None`
+pub fn mod_inverse(a: &BigInt, m: &BigInt) -> Option<BigInt> {
+    let (d, u) = extended_euclid(a, m);
+    if d == *ONE {
+        Some(u)
     } else {
-        (a, BigInt::from_slice(&[1]), BigInt::from_slice(&[0]))
+        None
     }
 }
 
@@ -66,7 +331,7 @@ fn is_prime(n: u64) -> bool {
         return false;
     }
     for i in 2..=n / 2 {
-        if n % i == 0 {
+        if n.is_multiple_of(i) {
             return false;
         }
     }
@@ -84,7 +349,19 @@ fn small_primes() -> [u64; 1229] {
     arr
 }
 
-pub fn miller_rabin(n: &BigInt) -> bool {
+/// Miller-Rabin 测试过程中的事件
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrimalityEvent {
+    /// 能被小素数整除，没有进入 Miller-Rabin 测试
+    SieveRejected { divisor: u64 },
+    /// 即将进行第 `round` 轮测试，从 1 开始
+    Round { round: usize },
+    /// 第 `round` 轮测试找到了合数的证据
+    Rejected { round: usize },
+}
+
+/// 测试过程中调用 `on_event`，返回 false 时提前停止，结果视为非素数
+pub fn miller_rabin(n: &BigInt, mut on_event: impl FnMut(PrimalityEvent) -> bool) -> bool {
     const MR_TEST_TIMES: usize = 64;
     lazy_static! {
         static ref SMALL_PRIMES: [u64; 1229] = small_primes();
@@ -95,63 +372,219 @@ pub fn miller_rabin(n: &BigInt) -> bool {
         return true;
     }
     for small_prime in *SMALL_PRIMES {
-        let mut base: u64 = 1;
-        let mut sum: u64 = 0;
-        for val in &n.value {
-            sum += (base * (val % small_prime)) % small_prime;
-            sum %= small_prime;
-            base *= (BigInt::VALUE_MASK + 1) % small_prime;
-            base %= small_prime;
-        }
-        if sum == 0 {
+        if n % small_prime == 0 {
+            on_event(PrimalityEvent::SieveRejected {
+                divisor: small_prime,
+            });
             return false;
         }
     }
 
     // n - 1 = 2^s * d
     let n_sub_1 = n - &ONE;
-    let mut least_nozero_idx = 0;
-    let mut s: u64 = 0;
-    for (i, v) in n_sub_1.value[0..n_sub_1.length].iter().enumerate() {
-        if *v != 0 {
-            least_nozero_idx = i;
-            break;
-        } else {
-            s += BigInt::VALUE_LEN;
-        }
-    }
-    for i in 0..BigInt::VALUE_LEN {
-        if (1 << i) & n_sub_1.value[least_nozero_idx] != 0 {
-            s += i;
-            break;
-        }
-    }
+    let s = n_sub_1.trailing_zeros();
     let d = &n_sub_1 >> s;
 
-    let barrett_m = n.barrett_m();
-    for _ in 0..MR_TEST_TIMES {
+    let ctx = ModPowContext::new(n, ModPowBackend::from_env());
+    for round in 1..=MR_TEST_TIMES {
+        if !on_event(PrimalityEvent::Round { round }) {
+            return false;
+        }
         let mut a;
         loop {
-            a = barrett_mod(&BigInt::rand(n.length), &barrett_m, n);
+            (_, a) = mod_div(&BigInt::rand(n.length), n);
             if a != *ONE {
                 break;
             }
         }
         // a^d
-        let mut cond = mod_power(&a, &d, &barrett_m, n);
+        let mut cond = ctx.mod_power(&a, &d);
         if cond != *ONE && cond != n_sub_1 {
             let mut ok = false;
             for _ in 1..s {
-                cond = barrett_mod(&(&cond * &cond), &barrett_m, n);
+                cond = ctx.mod_mul(&cond, &cond);
                 if cond == n_sub_1 {
                     ok = true;
                     break;
                 }
             }
             if !ok {
+                on_event(PrimalityEvent::Rejected { round });
                 return false;
             }
         }
     }
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 直接用 `mod_div` 取模的平方-乘算法，作为对照
+    fn naive_mod_power(a: &BigInt, b: &BigInt, n: &BigInt) -> BigInt {
+        let mut res = mod_div(&ONE, n).1;
+        for i in (0..b.bitlen()).rev() {
+            res = mod_div(&(&res * &res), n).1;
+            if (b.value[i / BigInt::VALUE_LEN as usize] >> (i % BigInt::VALUE_LEN as usize)) & 1
+                == 1
+            {
+                res = mod_div(&(&res * a), n).1;
+            }
+        }
+        res
+    }
+
+    fn odd_modulus(length: usize) -> BigInt {
+        let mut n = BigInt::rand(length);
+        n.value[0] |= 1;
+        n
+    }
+
+    fn check_backends(a: &BigInt, b: &BigInt, n: &BigInt) {
+        let expected = naive_mod_power(a, b, n);
+        for backend in [ModPowBackend::Barrett, ModPowBackend::Montgomery] {
+            let ctx = ModPowContext::new(n, backend);
+            assert_eq!(ctx.mod_power(a, b), expected, "{backend:?} {}", n.length);
+        }
+    }
+
+    #[test]
+    fn backends_agree() {
+        for length in [1, 2, 3, 8, 16] {
+            let n = odd_modulus(length);
+            for _ in 0..4 {
+                let a = mod_div(&BigInt::rand(length), &n).1;
+                check_backends(&a, &BigInt::rand(length), &n);
+                check_backends(&a, &BigInt::from_slice(&[65537]), &n);
+            }
+        }
+    }
+
+    #[test]
+    fn backends_agree_on_edge_cases() {
+        let zero = BigInt::from_slice(&[0]);
+        for n in [
+            BigInt::from_slice(&[3]),
+            BigInt::from_slice(&[u64::MAX]),
+            odd_modulus(1),
+            odd_modulus(4),
+            BigInt::from_slice(&[u64::MAX; 4]),
+        ] {
+            let e = BigInt::rand(2);
+            check_backends(&BigInt::rand(n.length), &zero, &n);
+            check_backends(&zero, &e, &n);
+            check_backends(&ONE, &e, &n);
+            check_backends(&(&n - &ONE), &e, &n);
+            // 底数不小于模数
+            check_backends(&n, &e, &n);
+            check_backends(&(&n + &ONE), &e, &n);
+            check_backends(&(&(&n * &n) - &ONE), &e, &n);
+        }
+    }
+
+    #[test]
+    fn backends_agree_on_mod_mul() {
+        for length in [1, 2, 5] {
+            let n = odd_modulus(length);
+            let a = mod_div(&BigInt::rand(length), &n).1;
+            let b = mod_div(&BigInt::rand(length), &n).1;
+            let expected = mod_div(&(&a * &b), &n).1;
+            for backend in [ModPowBackend::Barrett, ModPowBackend::Montgomery] {
+                assert_eq!(ModPowContext::new(&n, backend).mod_mul(&a, &b), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn gcd_matches_binary_gcd() {
+        for (a_len, b_len) in [(1, 1), (2, 1), (4, 4), (8, 3), (16, 16)] {
+            let g = BigInt::rand(1);
+            let a = &BigInt::rand(a_len) * &g;
+            let b = &BigInt::rand(b_len) * &g;
+            let d = gcd(&a, &b);
+            assert_eq!(binary_gcd(&a, &b), d);
+            assert_eq!(binary_gcd(&b, &a), d);
+            assert!(mod_div(&a, &d).1.is_zero() && mod_div(&b, &d).1.is_zero());
+            assert!(mod_div(&d, &g).1.is_zero());
+        }
+    }
+
+    #[test]
+    fn gcd_and_lcm_with_zero_and_one() {
+        let zero = BigInt::from_slice(&[0]);
+        let a = BigInt::rand(3);
+        for f in [gcd, binary_gcd] {
+            assert_eq!(f(&a, &zero), a);
+            assert_eq!(f(&zero, &a), a);
+            assert_eq!(f(&a, &ONE), *ONE);
+            assert_eq!(f(&ONE, &a), *ONE);
+            assert!(f(&zero, &zero).is_zero());
+        }
+        assert!(lcm(&a, &zero).is_zero());
+        assert!(lcm(&zero, &a).is_zero());
+        assert_eq!(lcm(&a, &ONE), a);
+        assert_eq!(lcm(&a, &a), a);
+    }
+
+    #[test]
+    fn lcm_is_common_multiple() {
+        let g = BigInt::rand(1);
+        let a = &BigInt::rand(3) * &g;
+        let b = &BigInt::rand(2) * &g;
+        let l = lcm(&a, &b);
+        assert!(mod_div(&l, &a).1.is_zero() && mod_div(&l, &b).1.is_zero());
+        assert_eq!(&l * &gcd(&a, &b), &a * &b);
+    }
+
+    #[test]
+    fn mod_inverse_round_trip() {
+        for length in [1, 2, 8] {
+            // 奇数模数下 2 的幂总是可逆
+            let m = odd_modulus(length);
+            for a in [TWO.clone(), mod_div(&BigInt::rand(length), &m).1] {
+                let (d, u) = extended_euclid(&a, &m);
+                assert!(u < m);
+                assert_eq!(mod_div(&(&u * &a), &m).1, d);
+                if d == *ONE {
+                    assert_eq!(mod_inverse(&a, &m), Some(u));
+                } else {
+                    assert_eq!(mod_inverse(&a, &m), None);
+                }
+            }
+            let u = mod_inverse(&TWO, &m).unwrap();
+            assert_eq!(mod_div(&(&u * &*TWO), &m).1, *ONE);
+        }
+        let e = BigInt::from_slice(&[65537]);
+        let m = BigInt::rand(16);
+        if let Some(d) = mod_inverse(&e, &m) {
+            assert_eq!(mod_div(&(&d * &e), &m).1, *ONE);
+        }
+    }
+
+    #[test]
+    fn mod_inverse_rejects_non_coprime() {
+        let zero = BigInt::from_slice(&[0]);
+        let g = &BigInt::rand(1) + &TWO;
+        let m = &BigInt::rand(4) * &g;
+        assert_eq!(mod_inverse(&(&BigInt::rand(2) * &g), &m), None);
+        assert_eq!(mod_inverse(&zero, &m), None);
+        assert_eq!(mod_inverse(&m, &m), None);
+        assert_eq!(mod_inverse(&ONE, &m), Some(ONE.clone()));
+        assert_eq!(mod_inverse(&(&m + &ONE), &m), Some(ONE.clone()));
+        // 偶数模数下偶数不可逆
+        assert_eq!(mod_inverse(&TWO, &BigInt::from_slice(&[10])), None);
+        assert_eq!(
+            mod_inverse(&THREE, &BigInt::from_slice(&[10])),
+            Some(BigInt::from_slice(&[7]))
+        );
+    }
+
+    #[test]
+    fn even_modulus_falls_back_to_barrett() {
+        let n = &odd_modulus(3) + &ONE;
+        let ctx = ModPowContext::new(&n, ModPowBackend::Montgomery);
+        assert!(matches!(ctx, ModPowContext::Barrett { .. }));
+        check_backends(&BigInt::rand(3), &BigInt::rand(1), &n);
+    }
+}
@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use rocket::tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::bigint::BigInt;
+use crate::error::Error;
+use crate::key_format::KeyFormat;
+use crate::keystore::Keystore;
+use crate::rsa::{self, KeyGenEvent, KeyGenProgress};
+
+use super::models::{ErrorRsp, JobStatus, KeyGenJobRsp, Keys};
+
+const JOB_ID_LEN: usize = 16;
+/// 已结束的任务保留一小时，之后在提交新任务时清理
+const JOB_TTL: Duration = Duration::from_secs(60 * 60);
+const NOT_FOUND: Error = Error::NotFound("Job not found");
+/// 每个任务缓存的事件数，订阅者跟不上时丢弃较早的事件
+const EVENT_CAPACITY: usize = 1024;
+/// 排队等待的任务数上限，队列已满时拒绝新任务
+const QUEUE_CAPACITY: usize = 64;
+/// 保留的任务数上限，包括已结束但未过期的任务
+const MAX_JOBS: usize = 1024;
+
+/// 一个密钥生成任务的参数
+pub struct KeyGenParams {
+    pub len: usize,
+    pub e: BigInt,
+    pub format: KeyFormat,
+    pub passphrase: Option<String>,
+    pub alg: Option<String>,
+    /// 生成后保存到密钥库
+    pub store: bool,
+}
+
+enum JobState {
+    Queued,
+    Running {
+        started: Instant,
+    },
+    /// 被取消时结果为 `Error::Cancelled`，保存到密钥库时带有 `key_id`
+    Finished {
+        result: Result<(Keys, Option<String>), Error>,
+        time_taken: u128,
+        at: Instant,
+    },
+}
+
+#[derive(Clone, Copy)]
+enum JobEvent {
+    Progress(KeyGenEvent),
+    Finished,
+}
+
+struct Job {
+    id: String,
+    params: KeyGenParams,
+    keystore: Arc<Keystore>,
+    progress: KeyGenProgress,
+    state: Mutex<JobState>,
+    events: broadcast::Sender<JobEvent>,
+}
+
+impl Job {
+    fn run(&self) {
+        let started = Instant::now();
+        {
+            let mut state = self.state.lock().unwrap();
+            // 排队时已被取消
+            if !matches!(*state, JobState::Queued) {
+                return;
+            }
+            *state = JobState::Running { started };
+        }
+
+        let params = &self.params;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let key = rsa::gen_keys_with_progress(params.len, &params.e, &self.progress)?;
+            let keys = Keys::encode(
+                &key,
+                params.format,
+                params.passphrase.as_deref(),
+                params.alg.as_deref(),
+            )?;
+            let key_id = match params.store {
+                true => Some(self.keystore.insert(key)?),
+                false => None,
+            };
+            Ok((keys, key_id))
+        }))
+        .unwrap_or(Err(Error::Internal("Key generation failed")));
+
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, JobState::Running { .. }) {
+            *state = JobState::Finished {
+                result,
+                time_taken: started.elapsed().as_millis(),
+                at: Instant::now(),
+            };
+            // 没有订阅者时发送失败，可以忽略
+            let _ = self.events.send(JobEvent::Finished);
+        }
+    }
+
+    /// 任务已经结束时返回 false
+    fn cancel(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let time_taken = match *state {
+            JobState::Queued => 0,
+            JobState::Running { started } => started.elapsed().as_millis(),
+            JobState::Finished { .. } => return false,
+        };
+        self.progress.cancel();
+        *state = JobState::Finished {
+            result: Err(Error::Cancelled),
+            time_taken,
+            at: Instant::now(),
+        };
+        let _ = self.events.send(JobEvent::Finished);
+        true
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), JobState::Finished { .. })
+    }
+
+    fn is_expired(&self) -> bool {
+        match *self.state.lock().unwrap() {
+            JobState::Finished { at, .. } => at.elapsed() > JOB_TTL,
+            _ => false,
+        }
+    }
+
+    fn status(&self) -> KeyGenJobRsp {
+        let state = self.state.lock().unwrap();
+        let (status, keys, error, time_taken) = match &*state {
+            JobState::Queued => (JobStatus::Queued, None, None, 0),
+            JobState::Running { started } => (
+                JobStatus::Running,
+                None,
+                None,
+                started.elapsed().as_millis(),
+            ),
+            JobState::Finished {
+                result, time_taken, ..
+            } => match result {
+                Ok((keys, _)) => (JobStatus::Done, Some(keys.clone()), None, *time_taken),
+                Err(Error::Cancelled) => (JobStatus::Cancelled, None, None, *time_taken),
+                Err(e) => (
+                    JobStatus::Failed,
+                    None,
+                    Some(ErrorRsp::from(*e)),
+                    *time_taken,
+                ),
+            },
+        };
+        let key_id = match &*state {
+            JobState::Finished {
+                result: Ok((_, key_id)),
+                ..
+            } => key_id.clone(),
+            _ => None,
+        };
+        KeyGenJobRsp {
+            job_id: self.id.clone(),
+            status,
+            bits: self.params.len,
+            candidates: self.progress.candidates(),
+            mr_rounds: self.progress.mr_rounds(),
+            keys,
+            key_id,
+            error,
+            time_taken,
+        }
+    }
+}
+
+/// 订阅一个任务的进度事件
+pub struct JobEvents {
+    job: Arc<Job>,
+    receiver: broadcast::Receiver<JobEvent>,
+}
+
+impl JobEvents {
+    pub fn status(&self) -> KeyGenJobRsp {
+        self.job.status()
+    }
+
+    /// 等待下一个事件，任务结束后返回 `None`
+    pub async fn next(&mut self) -> Option<KeyGenEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(JobEvent::Progress(event)) => return Some(event),
+                Ok(JobEvent::Finished) | Err(RecvError::Closed) => return None,
+                Err(RecvError::Lagged(_)) => continue,
+            }
+        }
+    }
+}
+
+/// 在固定数量的专用线程上排队执行的密钥生成任务
+pub struct KeyGenJobs {
+    jobs: Mutex<HashMap<String, Arc<Job>>>,
+    queue: SyncSender<Arc<Job>>,
+    keystore: Arc<Keystore>,
+}
+
+impl KeyGenJobs {
+    /// 启动 `workers` 个工作线程，同时最多运行 `workers` 个任务
+    pub fn new(workers: usize, keystore: Arc<Keystore>) -> Self {
+        let (queue, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..workers.max(1) {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("keygen-{i}"))
+                .spawn(move || worker(receiver))
+                .expect("Failed to spawn keygen worker");
+        }
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            queue,
+            keystore,
+        }
+    }
+
+    /// 工作线程数取自 `RSA_KEYGEN_WORKERS`，默认为 CPU 核数
+    pub fn from_env(keystore: Arc<Keystore>) -> Self {
+        let workers = env::var("RSA_KEYGEN_WORKERS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+        Self::new(workers, keystore)
+    }
+
+    /// 排队或保留的任务过多时返回 `Error::Busy`
+    pub fn submit(&self, params: KeyGenParams) -> Result<KeyGenJobRsp, Error> {
+        let mut id = [0u8; JOB_ID_LEN];
+        rand::thread_rng().fill(&mut id);
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let sender = events.clone();
+        let job = Arc::new(Job {
+            id: id.iter().map(|b| format!("{b:02x}")).collect(),
+            params,
+            keystore: self.keystore.clone(),
+            progress: KeyGenProgress::with_observer(move |event| {
+                let _ = sender.send(JobEvent::Progress(event));
+            }),
+            state: Mutex::new(JobState::Queued),
+            events,
+        });
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, job| !job.is_expired());
+        if jobs.len() >= MAX_JOBS {
+            return Err(Error::Busy("Too many jobs"));
+        }
+        match self.queue.try_send(job.clone()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => return Err(Error::Busy("Too many queued jobs")),
+            Err(TrySendError::Disconnected(_)) => {
+                return Err(Error::Internal("Keygen workers have stopped"))
+            }
+        }
+        jobs.insert(job.id.clone(), job.clone());
+        Ok(job.status())
+    }
+
+    pub fn status(&self, id: &str) -> Result<KeyGenJobRsp, Error> {
+        let jobs = self.jobs.lock().unwrap();
+        Ok(jobs.get(id).ok_or(NOT_FOUND)?.status())
+    }
+
+    /// 先订阅再读取状态，不会错过订阅之后的事件
+    pub fn subscribe(&self, id: &str) -> Result<JobEvents, Error> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(id).ok_or(NOT_FOUND)?.clone();
+        Ok(JobEvents {
+            receiver: job.events.subscribe(),
+            job,
+        })
+    }
+
+    /// 等待任务结束并取出结果，之后删除该任务。
+    /// 返回密钥、密钥库中的 ID 和耗时
+    pub async fn wait(&self, id: &str) -> Result<(Keys, Option<String>, u128), Error> {
+        let mut events = self.subscribe(id)?;
+        // 先订阅再检查，不会错过结束事件
+        if !events.job.is_finished() {
+            while events.next().await.is_some() {}
+        }
+        self.jobs.lock().unwrap().remove(id);
+        let state = events.job.state.lock().unwrap();
+        match &*state {
+            JobState::Finished {
+                result, time_taken, ..
+            } => result
+                .clone()
+                .map(|(keys, key_id)| (keys, key_id, *time_taken)),
+            _ => Err(Error::Internal("Key generation failed")),
+        }
+    }
+
+    /// 取消未结束的任务，已结束的任务则直接删除，返回之后的状态
+    pub fn cancel(&self, id: &str) -> Result<KeyGenJobRsp, Error> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(id).ok_or(NOT_FOUND)?.clone();
+        if !job.cancel() {
+            jobs.remove(id);
+        }
+        Ok(job.status())
+    }
+}
+
+fn worker(receiver: Arc<Mutex<Receiver<Arc<Job>>>>) {
+    loop {
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(job) => job.run(),
+            Err(_) => return,
+        }
+    }
+}
@@ -1,2 +1,3 @@
+pub mod jobs;
 pub mod models;
 pub mod routes;
\ No newline at end of file
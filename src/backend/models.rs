@@ -1,7 +1,36 @@
+use crate::envelope::Envelope;
+use crate::error::Error;
+use crate::hash::HashAlgorithm;
+use crate::key_format::KeyFormat;
+use crate::merkle::MerkleSignature;
+use crate::rsa::{Padding, RsaPrivateKey, SignatureScheme};
+
+/// 请求与响应中明文 `message` 的编码方式，只影响明文，密文格式与之无关
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageEncoding {
+    #[default]
+    Utf8,
+    /// 标准 base64，用于二进制数据
+    Base64,
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct EncryptReq {
     pub message: String,
+    #[serde(default)]
     pub public_key: String,
+    /// 默认为 `oaep`，`none` 必须显式给出
+    #[serde(default)]
+    pub padding: Padding,
+    /// OAEP 的 label
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    /// 使用密钥库中的密钥，此时可以省略 `public_key`
+    #[serde(default)]
+    pub key_id: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -13,8 +42,24 @@ pub struct EncryptRsp {
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct DecryptReq {
     pub ciphertext: String,
+    #[serde(default)]
     pub public_key: String,
+    #[serde(default)]
     pub private_key: String,
+    /// 默认为 `oaep`，`none` 必须显式给出
+    #[serde(default)]
+    pub padding: Padding,
+    /// OAEP 的 label
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    /// 私钥为加密的 PKCS#8 时所需的口令
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// 使用密钥库中的密钥，此时可以省略 `public_key` 和 `private_key`
+    #[serde(default)]
+    pub key_id: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -23,23 +68,80 @@ pub struct DecryptRsp {
     pub time_taken: u128,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct Keys {
+    /// `n,e`、PEM、JWK 或 `ssh-rsa AAAA...`
     pub public_key: String,
+    /// `d`、带 CRT 参数的 `d,p,q,dp,dq,qinv`、PEM 或 JWK
     pub private_key: String,
+    /// OpenSSH 格式时给出 SHA256 和 MD5 指纹
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fingerprints: Vec<String>,
+}
+
+impl Keys {
+    /// 按 `format` 导出密钥对，`passphrase` 和 `alg` 的含义同 `/api/keygen`
+    pub fn encode(
+        private_key: &RsaPrivateKey,
+        format: KeyFormat,
+        passphrase: Option<&str>,
+        alg: Option<&str>,
+    ) -> Result<Self, Error> {
+        let public_key = private_key.public_key();
+        Ok(match format {
+            KeyFormat::Jwk if passphrase.is_none() => Keys {
+                public_key: public_key.to_jwk().with_alg(alg).to_string(),
+                private_key: private_key.to_jwk().with_alg(alg).to_string(),
+                fingerprints: vec![],
+            },
+            KeyFormat::OpenSsh => Keys {
+                public_key: public_key.encode(format),
+                private_key: private_key.encode(format, passphrase)?,
+                fingerprints: vec![
+                    public_key.fingerprint_sha256(),
+                    public_key.fingerprint_md5(),
+                ],
+            },
+            _ => Keys {
+                public_key: public_key.encode(format),
+                private_key: private_key.encode(format, passphrase)?,
+                fingerprints: vec![],
+            },
+        })
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct KeyGenRsp {
     pub keys: Keys,
+    /// `store=true` 时密钥在密钥库中的 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
     pub time_taken: u128,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct SignReq {
     pub message: String,
+    #[serde(default)]
     pub public_key: String,
+    #[serde(default)]
     pub private_key: String,
+    /// 默认为 `pkcs1`，`none` 是不安全的教科书 RSA，只在显式给出时使用
+    #[serde(default)]
+    pub scheme: SignatureScheme,
+    /// 签名前对消息使用的哈希算法
+    #[serde(default)]
+    pub hash: HashAlgorithm,
+    /// PSS 的盐长度，默认与摘要等长，密钥较短时取能容纳的最大长度
+    #[serde(default)]
+    pub salt_len: Option<usize>,
+    /// 私钥为加密的 PKCS#8 时所需的口令
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// 使用密钥库中的密钥，此时可以省略 `public_key` 和 `private_key`
+    #[serde(default)]
+    pub key_id: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -52,11 +154,198 @@ pub struct SignRsp {
 pub struct VerifySignReq {
     pub message: String,
     pub message_signed: String,
-    pub public_key: String
+    #[serde(default)]
+    pub public_key: String,
+    /// 默认为 `pkcs1`，`none` 是不安全的教科书 RSA，只在显式给出时使用
+    #[serde(default)]
+    pub scheme: SignatureScheme,
+    /// 签名前对消息使用的哈希算法
+    #[serde(default)]
+    pub hash: HashAlgorithm,
+    /// PSS 的盐长度，默认与摘要等长，密钥较短时取能容纳的最大长度
+    #[serde(default)]
+    pub salt_len: Option<usize>,
+    /// 使用密钥库中的密钥，此时可以省略 `public_key`
+    #[serde(default)]
+    pub key_id: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct VerifySignRsp {
     pub verified: bool,
-    pub time_taken: u128
-}
\ No newline at end of file
+    pub time_taken: u128,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SealReq {
+    pub message: String,
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    /// 使用密钥库中的密钥，此时可以省略 `public_key`
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SealRsp {
+    pub envelope: Envelope,
+    pub time_taken: u128,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct OpenReq {
+    pub envelope: Envelope,
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub private_key: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    /// 私钥为加密的 PKCS#8 时所需的口令
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// 使用密钥库中的密钥，此时可以省略 `public_key` 和 `private_key`
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct OpenRsp {
+    pub message: String,
+    pub time_taken: u128,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct KeyInfo {
+    pub key_id: String,
+    /// Unix 时间戳，单位为秒
+    pub created_at: u64,
+    pub bits: usize,
+    /// OpenSSH 格式的 SHA256 指纹
+    pub fingerprint: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ListKeysRsp {
+    pub keys: Vec<KeyInfo>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PublicKeyRsp {
+    pub key_id: String,
+    pub public_key: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct MerkleSignReq {
+    pub message: String,
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub private_key: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    /// 分块大小，1 KiB 至 16 MiB，默认 64 KiB
+    #[serde(default)]
+    pub chunk_size: Option<usize>,
+    #[serde(default)]
+    pub hash: HashAlgorithm,
+    /// 私钥为加密的 PKCS#8 时所需的口令
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// 使用密钥库中的密钥，此时可以省略 `public_key` 和 `private_key`
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct MerkleSignRsp {
+    pub signature: MerkleSignature,
+    /// 按块的顺序给出每一块的包含证明
+    pub proofs: Vec<Vec<String>>,
+    pub time_taken: u128,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct MerkleVerifyChunkReq {
+    pub signature: MerkleSignature,
+    pub index: usize,
+    /// 第 `index` 块的内容
+    pub chunk: String,
+    pub proof: Vec<String>,
+    #[serde(default)]
+    pub public_key: String,
+    #[serde(default)]
+    pub encoding: MessageEncoding,
+    /// 使用密钥库中的密钥，此时可以省略 `public_key`
+    #[serde(default)]
+    pub key_id: Option<String>,
+}
+
+/// 所有接口出错时返回的内容
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ErrorRsp {
+    /// 机器可读的错误码，如 `invalid_key`
+    pub error: String,
+    pub message: String,
+}
+
+impl From<Error> for ErrorRsp {
+    fn from(error: Error) -> Self {
+        Self {
+            error: error.code().to_owned(),
+            message: error.to_string(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct KeyGenJobReq {
+    pub len: usize,
+    #[serde(default)]
+    pub e: Option<u64>,
+    /// 同 `/api/keygen` 的 `format`，默认 `hex`
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    #[serde(default)]
+    pub alg: Option<String>,
+    /// 生成后保存到密钥库，同 `/api/keygen` 的 `store=true`
+    #[serde(default)]
+    pub store: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct KeyGenJobRsp {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub bits: usize,
+    /// 已尝试的素数候选个数
+    pub candidates: u64,
+    /// 已进行的 Miller-Rabin 测试轮数
+    pub mr_rounds: u64,
+    /// 状态为 `done` 时给出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys: Option<Keys>,
+    /// `store=true` 且状态为 `done` 时密钥在密钥库中的 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// 状态为 `failed` 时给出
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorRsp>,
+    /// 已运行的时间，排队的时间不计入
+    pub time_taken: u128,
+}
@@ -1,79 +1,467 @@
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::Instant;
 
-use crate::{bigint::BigInt, rsa};
-use rocket::{get, post, serde::json::Json};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 
+use crate::bigint::BigInt;
+use crate::envelope;
+use crate::error::Error;
+use crate::key_format::KeyFormat;
+use crate::keystore::Keystore;
+use crate::merkle::{self, MerkleTree};
+use crate::rsa::{self, Padding, RsaPrivateKey, RsaPublicKey};
+use rocket::http::Status;
+use rocket::response::stream::{Event, EventStream};
+use rocket::response::{self, status, Responder};
+use rocket::tokio::select;
+use rocket::{catch, delete, get, post, serde::json::Json, Request, Shutdown, State};
+
+use super::jobs::{KeyGenJobs, KeyGenParams};
 use super::models::*;
 
-#[get("/api/keygen/<len>")]
-pub fn key_gen(len: usize) -> Json<KeyGenRsp> {
-    let start = SystemTime::now();
-    let (public_key, private_key) = rsa::gen_keys(len);
-    let end = SystemTime::now();
-    Json(KeyGenRsp {
-        keys: Keys {
-            public_key: public_key.fmt_hex(),
-            private_key: private_key.fmt_hex(),
-        },
-        time_taken: end.duration_since(start).unwrap().as_millis(),
+/// 单次处理的明文上限，加解密逐块进行，过长的消息会长时间占用服务器
+const MAX_MESSAGE_LEN: usize = 64 * 1024;
+/// JSON 请求体的上限，超出时返回 413
+pub const MAX_REQUEST_LEN: u64 = 1024 * 1024;
+
+type ApiResult<T> = Result<Json<T>, Error>;
+
+/// 错误以 `{ "error": 错误码, "message": 说明 }` 返回，输入错误为 4xx
+impl<'r> Responder<'r, 'static> for Error {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        let status = match self {
+            Error::NotFound(_) => Status::NotFound,
+            Error::Busy(_) => Status::ServiceUnavailable,
+            Error::Internal(_) => Status::InternalServerError,
+            Error::InvalidKey(_)
+            | Error::MessageTooLong
+            | Error::Padding(_)
+            | Error::Decryption(_) => Status::UnprocessableEntity,
+            _ => Status::BadRequest,
+        };
+        (status, Json(ErrorRsp::from(self))).respond_to(req)
+    }
+}
+
+/// 请求体无法解析、路由不存在等由 Rocket 产生的错误也返回 JSON
+#[catch(default)]
+pub fn default_catcher(status: Status, _req: &Request) -> (Status, Json<ErrorRsp>) {
+    let reason = status.reason().unwrap_or("Unknown error");
+    let body = ErrorRsp {
+        error: reason.to_ascii_lowercase().replace(' ', "_"),
+        message: reason.to_owned(),
+    };
+    (status, Json(body))
+}
+
+fn decode_base64(input: &str) -> Result<Vec<u8>, Error> {
+    BASE64
+        .decode(input)
+        .map_err(|_| Error::Parse("Invalid base64"))
+}
+
+/// 明文、待签名的消息等不能超过 `MAX_MESSAGE_LEN`
+fn check_message_len(len: usize) -> Result<(), Error> {
+    if len > MAX_MESSAGE_LEN {
+        return Err(Error::InvalidInput("Message must not exceed 64 KiB"));
+    }
+    Ok(())
+}
+
+/// 密文的分块数不能超过最长明文加密后的分块数，解密的耗时与分块数成正比
+fn check_block_count(input: &str, key: &RsaPublicKey, padding: Padding) -> Result<(), Error> {
+    if input.split(',').count() > rsa::block_count(MAX_MESSAGE_LEN, key, padding)? {
+        return Err(Error::InvalidInput("Ciphertext has too many blocks"));
+    }
+    Ok(())
+}
+
+/// 给出 `key_id` 时从密钥库中读取公钥
+fn load_public_key(
+    keystore: &Keystore,
+    key_id: Option<&str>,
+    public_key: &str,
+) -> Result<RsaPublicKey, Error> {
+    match key_id {
+        Some(key_id) => keystore.public_key(key_id),
+        None => RsaPublicKey::parse(public_key),
+    }
+}
+
+/// 给出 `key_id` 时从密钥库中读取私钥
+fn load_private_key(
+    keystore: &Keystore,
+    key_id: Option<&str>,
+    public_key: &str,
+    private_key: &str,
+    passphrase: Option<&str>,
+) -> Result<RsaPrivateKey, Error> {
+    match key_id {
+        Some(key_id) => keystore.private_key(key_id),
+        None => RsaPrivateKey::parse(RsaPublicKey::parse(public_key)?, private_key, passphrase),
+    }
+}
+
+/// 在生成之前检查参数，避免生成之后才失败
+fn keygen_params(
+    len: usize,
+    e: Option<u64>,
+    format: Option<&str>,
+    passphrase: Option<String>,
+    alg: Option<String>,
+    store: bool,
+) -> Result<KeyGenParams, Error> {
+    rsa::check_key_len(len)?;
+    let format = format.unwrap_or("hex").parse()?;
+    if passphrase.is_some() && !matches!(format, KeyFormat::Pkcs8 | KeyFormat::OpenSsh) {
+        return Err(Error::Unsupported(
+            "Only PKCS#8 private keys can be encrypted",
+        ));
+    }
+    Ok(KeyGenParams {
+        len,
+        e: BigInt::from_slice(&[e.unwrap_or(rsa::DEFAULT_E)]),
+        format,
+        passphrase,
+        alg,
+        store,
+    })
+}
+
+/// `format` 可选 `hex`（默认）、`pkcs1`、`pkcs8`、`jwk`、`openssh`，
+/// `pkcs8` 和 `openssh` 可以再给出 `passphrase` 加密私钥，`jwk` 可以给出 `alg`，
+/// `store=true` 时同时保存到密钥库。
+/// 与后台任务共用工作线程，等待生成结束后返回
+#[get("/api/keygen/<len>?<e>&<format>&<passphrase>&<alg>&<store>")]
+pub async fn key_gen(
+    jobs: &State<KeyGenJobs>,
+    len: usize,
+    e: Option<u64>,
+    format: Option<&str>,
+    passphrase: Option<&str>,
+    alg: Option<&str>,
+    store: Option<bool>,
+) -> ApiResult<KeyGenRsp> {
+    let params = keygen_params(
+        len,
+        e,
+        format,
+        passphrase.map(str::to_owned),
+        alg.map(str::to_owned),
+        store.unwrap_or(false),
+    )?;
+    let job = jobs.submit(params)?;
+    let (keys, key_id, time_taken) = jobs.wait(&job.job_id).await?;
+    Ok(Json(KeyGenRsp {
+        keys,
+        key_id,
+        time_taken,
+    }))
+}
+
+/// 在后台生成密钥，立即返回任务 ID，参数同 `/api/keygen`
+#[post("/api/keygen/jobs", data = "<keygen_job_req>")]
+pub fn create_keygen_job(
+    jobs: &State<KeyGenJobs>,
+    keygen_job_req: Json<KeyGenJobReq>,
+) -> Result<status::Accepted<Json<KeyGenJobRsp>>, Error> {
+    let keygen_job_req = keygen_job_req.into_inner();
+    let params = keygen_params(
+        keygen_job_req.len,
+        keygen_job_req.e,
+        keygen_job_req.format.as_deref(),
+        keygen_job_req.passphrase,
+        keygen_job_req.alg,
+        keygen_job_req.store,
+    )?;
+    Ok(status::Accepted(Json(jobs.submit(params)?)))
+}
+
+#[get("/api/keygen/jobs/<job_id>")]
+pub fn get_keygen_job(jobs: &State<KeyGenJobs>, job_id: &str) -> ApiResult<KeyGenJobRsp> {
+    Ok(Json(jobs.status(job_id)?))
+}
+
+/// 以 Server-Sent Events 推送素数搜索的过程。
+/// 开始和结束时各推送一次 `status` 事件，内容同查询任务的结果，其间推送 `progress` 事件
+#[get("/api/keygen/jobs/<job_id>/events")]
+pub fn keygen_job_events(
+    jobs: &State<KeyGenJobs>,
+    job_id: &str,
+    mut end: Shutdown,
+) -> Result<EventStream![], Error> {
+    let mut events = jobs.subscribe(job_id)?;
+    Ok(EventStream! {
+        let status = events.status();
+        yield Event::json(&status).event("status");
+        if matches!(status.status, JobStatus::Queued | JobStatus::Running) {
+            loop {
+                let event = select! {
+                    event = events.next() => event,
+                    _ = &mut end => None,
+                };
+                match event {
+                    Some(event) => yield Event::json(&event).event("progress"),
+                    None => break,
+                }
+            }
+            yield Event::json(&events.status()).event("status");
+        }
     })
 }
 
+/// 取消未结束的任务，已结束的任务则删除
+#[delete("/api/keygen/jobs/<job_id>")]
+pub fn cancel_keygen_job(jobs: &State<KeyGenJobs>, job_id: &str) -> ApiResult<KeyGenJobRsp> {
+    Ok(Json(jobs.cancel(job_id)?))
+}
+
 #[post("/api/encrypt", data = "<encrypt_req>")]
-pub fn encrypt(encrypt_req: Json<EncryptReq>) -> Json<EncryptRsp> {
-    let start = SystemTime::now();
-    let public_key = BigInt::from_hex(&encrypt_req.public_key).unwrap();
-    let m = public_key.barrett_m();
-    let ciphertext = rsa::encrypt(&encrypt_req.message, &public_key, &m);
-    let end = SystemTime::now();
-    Json(EncryptRsp {
+pub fn encrypt(
+    keystore: &State<Arc<Keystore>>,
+    encrypt_req: Json<EncryptReq>,
+) -> ApiResult<EncryptRsp> {
+    let start = Instant::now();
+    let public_key = load_public_key(
+        keystore,
+        encrypt_req.key_id.as_deref(),
+        &encrypt_req.public_key,
+    )?;
+    let label = encrypt_req.label.as_bytes();
+    let message = match encrypt_req.encoding {
+        MessageEncoding::Utf8 => encrypt_req.message.as_bytes().to_vec(),
+        MessageEncoding::Base64 => decode_base64(&encrypt_req.message)?,
+    };
+    check_message_len(message.len())?;
+    let ciphertext = rsa::encrypt_bytes(&message, &public_key, encrypt_req.padding, label)?;
+    Ok(Json(EncryptRsp {
         ciphertext,
-        time_taken: end.duration_since(start).unwrap().as_millis(),
-    })
+        time_taken: start.elapsed().as_millis(),
+    }))
 }
 
 #[post("/api/decrypt", data = "<decrypt_req>")]
-pub fn decrypt(decrypt_req: Json<DecryptReq>) -> Json<DecryptRsp> {
-    let start = SystemTime::now();
-    let public_key = BigInt::from_hex(&decrypt_req.public_key).unwrap();
-    let m = public_key.barrett_m();
-    let private_key = BigInt::from_hex(&decrypt_req.private_key).unwrap();
-    let message = rsa::decrypt(&decrypt_req.ciphertext, &public_key, &m, &private_key);
-    let end = SystemTime::now();
-    Json(DecryptRsp {
+pub fn decrypt(
+    keystore: &State<Arc<Keystore>>,
+    decrypt_req: Json<DecryptReq>,
+) -> ApiResult<DecryptRsp> {
+    let start = Instant::now();
+    let private_key = load_private_key(
+        keystore,
+        decrypt_req.key_id.as_deref(),
+        &decrypt_req.public_key,
+        &decrypt_req.private_key,
+        decrypt_req.passphrase.as_deref(),
+    )?;
+    check_block_count(
+        &decrypt_req.ciphertext,
+        &private_key.public_key(),
+        decrypt_req.padding,
+    )?;
+    let label = decrypt_req.label.as_bytes();
+    let message = rsa::decrypt_bytes(
+        &decrypt_req.ciphertext,
+        &private_key,
+        decrypt_req.padding,
+        label,
+    )?;
+    let message = match decrypt_req.encoding {
+        MessageEncoding::Utf8 => {
+            String::from_utf8(message).map_err(|_| Error::Parse("Message is not valid UTF-8"))?
+        }
+        MessageEncoding::Base64 => BASE64.encode(message),
+    };
+    Ok(Json(DecryptRsp {
         message,
-        time_taken: end.duration_since(start).unwrap().as_millis(),
-    })
+        time_taken: start.elapsed().as_millis(),
+    }))
 }
 
 #[post("/api/sign", data = "<sign_req>")]
-pub fn sign(sign_req: Json<SignReq>) -> Json<SignRsp> {
-    let start = SystemTime::now();
-    let public_key = BigInt::from_hex(&sign_req.public_key).unwrap();
-    let m = public_key.barrett_m();
-    let private_key = BigInt::from_hex(&sign_req.private_key).unwrap();
-    let message_signed = rsa::sign(&sign_req.message, &public_key, &m, &private_key);
-    let end = SystemTime::now();
-    Json(SignRsp {
-        message_signed: message_signed,
-        time_taken: end.duration_since(start).unwrap().as_millis(),
-    })
+pub fn sign(keystore: &State<Arc<Keystore>>, sign_req: Json<SignReq>) -> ApiResult<SignRsp> {
+    let start = Instant::now();
+    check_message_len(sign_req.message.len())?;
+    let private_key = load_private_key(
+        keystore,
+        sign_req.key_id.as_deref(),
+        &sign_req.public_key,
+        &sign_req.private_key,
+        sign_req.passphrase.as_deref(),
+    )?;
+    let message_signed = rsa::sign(
+        &sign_req.message,
+        &private_key,
+        sign_req.scheme,
+        sign_req.hash,
+        sign_req.salt_len,
+    )?;
+    Ok(Json(SignRsp {
+        message_signed,
+        time_taken: start.elapsed().as_millis(),
+    }))
 }
 
 #[post("/api/verify_sign", data = "<verify_sign_req>")]
-pub fn verify_sign(verify_sign_req: Json<VerifySignReq>) -> Json<VerifySignRsp> {
-    let start = SystemTime::now();
-    let public_key = BigInt::from_hex(&verify_sign_req.public_key).unwrap();
-    let m = public_key.barrett_m();
-    let (verified, _) = rsa::ver_sign(
+pub fn verify_sign(
+    keystore: &State<Arc<Keystore>>,
+    verify_sign_req: Json<VerifySignReq>,
+) -> ApiResult<VerifySignRsp> {
+    let start = Instant::now();
+    let public_key = load_public_key(
+        keystore,
+        verify_sign_req.key_id.as_deref(),
+        &verify_sign_req.public_key,
+    )?;
+    check_message_len(verify_sign_req.message.len())?;
+    // `none` 方案的签名与无填充的密文分块方式相同，其余方案只有一块
+    check_block_count(&verify_sign_req.message_signed, &public_key, Padding::None)?;
+    let verified = rsa::ver_sign(
         &verify_sign_req.message,
         &verify_sign_req.message_signed,
         &public_key,
-        &m,
-    );
-    let end = SystemTime::now();
-    Json(VerifySignRsp {
+        verify_sign_req.scheme,
+        verify_sign_req.hash,
+        verify_sign_req.salt_len,
+    )?;
+    Ok(Json(VerifySignRsp {
         verified,
-        time_taken: end.duration_since(start).unwrap().as_millis(),
-    })
+        time_taken: start.elapsed().as_millis(),
+    }))
+}
+
+#[post("/api/seal", data = "<seal_req>")]
+pub fn seal(keystore: &State<Arc<Keystore>>, seal_req: Json<SealReq>) -> ApiResult<SealRsp> {
+    let start = Instant::now();
+    let public_key = load_public_key(keystore, seal_req.key_id.as_deref(), &seal_req.public_key)?;
+    let message = match seal_req.encoding {
+        MessageEncoding::Utf8 => seal_req.message.as_bytes().to_vec(),
+        MessageEncoding::Base64 => decode_base64(&seal_req.message)?,
+    };
+    check_message_len(message.len())?;
+    let envelope = envelope::seal(&message, &public_key)?;
+    Ok(Json(SealRsp {
+        envelope,
+        time_taken: start.elapsed().as_millis(),
+    }))
+}
+
+#[post("/api/open", data = "<open_req>")]
+pub fn open(keystore: &State<Arc<Keystore>>, open_req: Json<OpenReq>) -> ApiResult<OpenRsp> {
+    let start = Instant::now();
+    let private_key = load_private_key(
+        keystore,
+        open_req.key_id.as_deref(),
+        &open_req.public_key,
+        &open_req.private_key,
+        open_req.passphrase.as_deref(),
+    )?;
+    check_message_len(open_req.envelope.message_len())?;
+    let message = envelope::open(&open_req.envelope, &private_key)?;
+    let message = match open_req.encoding {
+        MessageEncoding::Utf8 => {
+            String::from_utf8(message).map_err(|_| Error::Parse("Message is not valid UTF-8"))?
+        }
+        MessageEncoding::Base64 => BASE64.encode(message),
+    };
+    Ok(Json(OpenRsp {
+        message,
+        time_taken: start.elapsed().as_millis(),
+    }))
+}
+
+#[get("/api/keys")]
+pub fn list_keys(keystore: &State<Arc<Keystore>>) -> Json<ListKeysRsp> {
+    let keys = keystore
+        .list()
+        .into_iter()
+        .map(|(key_id, created_at, public_key)| KeyInfo {
+            key_id,
+            created_at,
+            bits: public_key.n.bitlen(),
+            fingerprint: public_key.fingerprint_sha256(),
+        })
+        .collect();
+    Json(ListKeysRsp { keys })
+}
+
+/// `format` 与 `/api/keygen` 相同
+#[get("/api/keys/<key_id>?<format>")]
+pub fn get_public_key(
+    keystore: &State<Arc<Keystore>>,
+    key_id: &str,
+    format: Option<&str>,
+) -> ApiResult<PublicKeyRsp> {
+    let format: KeyFormat = format.unwrap_or("hex").parse()?;
+    let public_key = keystore.public_key(key_id)?;
+    Ok(Json(PublicKeyRsp {
+        key_id: key_id.to_owned(),
+        public_key: public_key.encode(format),
+    }))
+}
+
+#[delete("/api/keys/<key_id>")]
+pub fn delete_key(keystore: &State<Arc<Keystore>>, key_id: &str) -> ApiResult<PublicKeyRsp> {
+    let public_key = keystore.remove(key_id)?;
+    Ok(Json(PublicKeyRsp {
+        key_id: key_id.to_owned(),
+        public_key: public_key.fmt_hex(),
+    }))
+}
+
+#[post("/api/merkle/sign", data = "<merkle_sign_req>")]
+pub fn merkle_sign(
+    keystore: &State<Arc<Keystore>>,
+    merkle_sign_req: Json<MerkleSignReq>,
+) -> ApiResult<MerkleSignRsp> {
+    let start = Instant::now();
+    let private_key = load_private_key(
+        keystore,
+        merkle_sign_req.key_id.as_deref(),
+        &merkle_sign_req.public_key,
+        &merkle_sign_req.private_key,
+        merkle_sign_req.passphrase.as_deref(),
+    )?;
+    let message = match merkle_sign_req.encoding {
+        MessageEncoding::Utf8 => merkle_sign_req.message.as_bytes().to_vec(),
+        MessageEncoding::Base64 => decode_base64(&merkle_sign_req.message)?,
+    };
+    check_message_len(message.len())?;
+    let tree = MerkleTree::from_reader(
+        message.as_slice(),
+        merkle_sign_req
+            .chunk_size
+            .unwrap_or(merkle::DEFAULT_CHUNK_SIZE),
+        merkle_sign_req.hash,
+    )?;
+    let signature = tree.sign(&private_key)?;
+    let proofs = (0..tree.chunk_count())
+        .map(|i| tree.proof(i))
+        .collect::<Result<_, _>>()?;
+    Ok(Json(MerkleSignRsp {
+        signature,
+        proofs,
+        time_taken: start.elapsed().as_millis(),
+    }))
+}
+
+#[post("/api/merkle/verify_chunk", data = "<merkle_verify_chunk_req>")]
+pub fn merkle_verify_chunk(
+    keystore: &State<Arc<Keystore>>,
+    merkle_verify_chunk_req: Json<MerkleVerifyChunkReq>,
+) -> ApiResult<VerifySignRsp> {
+    let start = Instant::now();
+    let req = &merkle_verify_chunk_req;
+    let public_key = load_public_key(keystore, req.key_id.as_deref(), &req.public_key)?;
+    let chunk = match req.encoding {
+        MessageEncoding::Utf8 => req.chunk.as_bytes().to_vec(),
+        MessageEncoding::Base64 => decode_base64(&req.chunk)?,
+    };
+    check_message_len(chunk.len())?;
+    let verified = req
+        .signature
+        .verify_chunk(&public_key, req.index, &chunk, &req.proof)?;
+    Ok(Json(VerifySignRsp {
+        verified,
+        time_taken: start.elapsed().as_millis(),
+    }))
 }
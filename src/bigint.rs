@@ -4,6 +4,8 @@ use std::ops;
 use lazy_static::lazy_static;
 use rand::Rng;
 
+use crate::error::Error;
+
 #[derive(Clone, Debug, Eq)]
 pub struct BigInt {
     pub value: Vec<u64>, // 从小到大
@@ -11,31 +13,40 @@ pub struct BigInt {
 }
 
 impl BigInt {
-    pub const VALUE_LEN: u64 = 32;
-    pub const VALUE_MASK: u64 = (((1 as u64) << Self::VALUE_LEN as u64) - 1);
-    pub const MAX_LEN: usize = 2048;
-
-    pub fn from_hex(hex: &str) -> Result<Self, &'static str> {
-        if hex.len() % 8 != 0 {
-            return Err("String length is not multiplication of 8");
+    pub const VALUE_LEN: u64 = 64;
+    pub const VALUE_MASK: u64 = u64::MAX;
+    /// 每个 limb 对应的十六进制字符数
+    pub const HEX_DIGITS: usize = Self::VALUE_LEN as usize / 4;
+    pub const MAX_LEN: usize = 1024;
+    /// 较短的乘数不少于该 limb 数时使用 Karatsuba
+    pub const KARATSUBA_THRESHOLD: usize = 16;
+    /// 较短的乘数不少于该 limb 数时使用 Toom-3
+    pub const TOOM3_THRESHOLD: usize = 512;
+
+    /// 长度只需是 8 的倍数，兼容 32 位 limb 时期输出的十六进制串
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        if !hex.len().is_multiple_of(8) {
+            return Err(Error::InvalidHex(
+                "String length is not multiplication of 8",
+            ));
         }
 
-        let length = hex.len() / 8;
+        let length = hex.len().div_ceil(Self::HEX_DIGITS);
         if length > Self::MAX_LEN {
-            return Err("Input is longer than 2048 * 8 * 32 bits");
+            return Err(Error::InvalidHex("Input is longer than 1024 * 64 bits"));
         }
-        let mut res = Self::with_capacity(length + 1);
-        res.length = length;
+        let mut res = Self::with_capacity(cmp::max(length, 1) + 1);
+        res.length = cmp::max(length, 1);
 
-        for (i, c) in hex.chars().enumerate() {
-            let value_idx = length - 1 - i / 8;
-            res.value[value_idx] *= 16;
-            res.value[value_idx] += match c {
+        for (i, c) in hex.chars().rev().enumerate() {
+            let digit = match c {
                 '0'..='9' => c as u64 - '0' as u64,
                 'a'..='f' => c as u64 - 'a' as u64 + 10,
-                _ => return Err("Invalid char in hex string"),
-            }
+                _ => return Err(Error::InvalidHex("Invalid char in hex string")),
+            };
+            res.value[i / Self::HEX_DIGITS] |= digit << (4 * (i % Self::HEX_DIGITS));
         }
+        res.remove_front_zeros();
         Ok(res)
     }
     pub fn with_capacity(capacity: usize) -> Self {
@@ -45,12 +56,7 @@ impl BigInt {
         }
     }
     pub fn from_slice(slice: &[u64]) -> Self {
-        let idx;
-        if slice.len() > Self::MAX_LEN {
-            idx = slice.len() - Self::MAX_LEN;
-        } else {
-            idx = 0;
-        }
+        let idx = slice.len().saturating_sub(Self::MAX_LEN);
         let value: Vec<u64> = slice[idx..].into();
         let len = value.len();
         Self { value, length: len }
@@ -60,7 +66,7 @@ impl BigInt {
         Self {
             value: (0..length)
                 .map(|i| {
-                    let val = rng.gen::<u32>() as u64;
+                    let val = rng.gen::<u64>();
                     // 确保最高 value 至少为 1，符合长度要求
                     if i != length - 1 || val > 0 {
                         val
@@ -75,26 +81,50 @@ impl BigInt {
     pub fn is_zero(&self) -> bool {
         self.length == 1 && self.value[0] == 0
     }
+    /// 二进制位数，0 的位数为 0
     pub fn bitlen(&self) -> usize {
-        let count_bits = |x: u64| -> usize {
-            if x == 0 {
-                return 1;
-            }
-            (usize::BITS - x.leading_zeros() - 1) as usize
-        };
-        (self.length - 1) * Self::VALUE_LEN as usize
-            + count_bits(self.value[self.length - 1])
+        let top = self.value[self.length - 1];
+        (self.length - 1) * Self::VALUE_LEN as usize + (u64::BITS - top.leading_zeros()) as usize
     }
-    pub fn to_int(&self) -> Result<u64, &str> {
-        let mut res: u64 = 0;
-        for i in 0..self.length {
-            let val = self.value[i] * (1 << (i * Self::VALUE_LEN as usize)) as u64;
-            if std::u64::MAX - res < val {
-                return Err("overflow");
-            }
-            res += val;
+    /// 字节数，即 ceil(bitlen / 8)
+    pub fn bytelen(&self) -> usize {
+        self.bitlen().div_ceil(8)
+    }
+    /// 从大端字节串读入 (OS2IP)
+    pub fn from_bytes_be(bytes: &[u8]) -> Self {
+        let value = bytes
+            .rchunks(Self::VALUE_LEN as usize / 8)
+            .map(|chunk| chunk.iter().fold(0, |acc, b| (acc << 8) | *b as u64))
+            .collect::<Vec<_>>();
+        if value.is_empty() {
+            return Self::with_capacity(1);
         }
-        Ok(res)
+        Self::from_limbs(value)
+    }
+    /// 输出大端字节串 (I2OSP)，不足 `len` 字节时在前面补 0
+    pub fn to_bytes_be(&self, len: usize) -> Vec<u8> {
+        let bytes = self.value[0..self.length]
+            .iter()
+            .rev()
+            .flat_map(|v| v.to_be_bytes())
+            .skip_while(|b| *b == 0)
+            .collect::<Vec<_>>();
+        let mut res = vec![0; len.saturating_sub(bytes.len())];
+        res.extend(bytes);
+        res
+    }
+    /// 末尾连续 0 bit 的个数，0 视为没有
+    pub fn trailing_zeros(&self) -> u64 {
+        match self.value[0..self.length].iter().position(|v| *v != 0) {
+            Some(i) => i as u64 * Self::VALUE_LEN + self.value[i].trailing_zeros() as u64,
+            None => 0,
+        }
+    }
+    pub fn to_int(&self) -> Result<u64, Error> {
+        if self.value[1..self.length].iter().any(|v| *v != 0) {
+            return Err(Error::InvalidInput("overflow"));
+        }
+        Ok(self.value[0])
     }
     pub fn clone_slice(&self, start: usize, end: usize) -> Self {
         let new_value = self.value[start..end].into();
@@ -107,7 +137,7 @@ impl BigInt {
         self.value[0..self.length]
             .iter()
             .rev()
-            .map(|v| format!("{:08x}", v))
+            .map(|v| format!("{:016x}", v))
             .collect::<Vec<_>>()
             .join("")
     }
@@ -120,7 +150,116 @@ impl BigInt {
         &(&Self::from_slice(&[1]) << k as u64) / self
     }
 
-    fn remove_front_zeros(&mut self) {
+    /// 平方，朴素算法下交叉项只需计算一次
+    pub fn square(&self) -> Self {
+        if self.is_zero() {
+            return Self::with_capacity(1);
+        }
+        let mut value = vec![0; 2 * self.length];
+        square_slice(&self.value[0..self.length], &mut value);
+        Self::from_limbs(value)
+    }
+
+    /// 朴素 O(n^2) 乘法
+    pub fn mul_schoolbook(&self, rhs: &Self) -> Self {
+        let mut value = vec![0; self.length + rhs.length];
+        mul_schoolbook_slice(
+            &self.value[0..self.length],
+            &rhs.value[0..rhs.length],
+            &mut value,
+        );
+        Self::from_limbs(value)
+    }
+
+    /// Karatsuba 乘法，低于阈值的子问题回到朴素乘法
+    pub fn mul_karatsuba(&self, rhs: &Self) -> Self {
+        let mut value = vec![0; self.length + rhs.length];
+        mul_slice(
+            &self.value[0..self.length],
+            &rhs.value[0..rhs.length],
+            &mut value,
+        );
+        Self::from_limbs(value)
+    }
+
+    /// Toom-3 乘法，插值顺序参考 Bodrato 的方案，取点 0, 1, -1, -2, ∞
+    pub fn mul_toom3(&self, rhs: &Self) -> Self {
+        let k = cmp::max(self.length, rhs.length).div_ceil(3);
+        let eval = |x: &BigInt| {
+            let x0 = Signed::from(x.limbs(0, k));
+            let x1 = Signed::from(x.limbs(k, 2 * k));
+            let x2 = Signed::from(x.limbs(2 * k, 3 * k));
+            let tmp = x0.add(&x2);
+            let p1 = tmp.add(&x1);
+            let pm1 = tmp.sub(&x1);
+            let pm2 = pm1.add(&x2).double().sub(&x0);
+            [x0, p1, pm1, pm2, x2]
+        };
+        let [p0, p1, pm1, pm2, pinf] = eval(self);
+        let [q0, q1, qm1, qm2, qinf] = eval(rhs);
+
+        let r0 = p0.mul(&q0);
+        let r1 = p1.mul(&q1);
+        let rm1 = pm1.mul(&qm1);
+        let rm2 = pm2.mul(&qm2);
+        let r4 = pinf.mul(&qinf);
+
+        let r3 = rm2.sub(&r1).div_exact(3);
+        let r1 = r1.sub(&rm1).div_exact(2);
+        let r2 = rm1.sub(&r0);
+        let r3 = r2.sub(&r3).div_exact(2).add(&r4.double());
+        let r2 = r2.add(&r1).sub(&r4);
+        let r1 = r1.sub(&r3);
+
+        let mut res = r4.mag.shl_limbs(4 * k);
+        res = &res + &r3.mag.shl_limbs(3 * k);
+        res = &res + &r2.mag.shl_limbs(2 * k);
+        res = &res + &r1.mag.shl_limbs(k);
+        &res + &r0.mag
+    }
+
+    /// 除以单个 limb，返回 `(商, 余数)`
+    pub fn div_rem_u64(&self, rhs: u64) -> (Self, u64) {
+        let mut value = self.value[0..self.length].to_vec();
+        let mut remain: u128 = 0;
+        for v in value.iter_mut().rev() {
+            let cur = (remain << Self::VALUE_LEN) | *v as u128;
+            *v = (cur / rhs as u128) as u64;
+            remain = cur % rhs as u128;
+        }
+        (Self::from_limbs(value), remain as u64)
+    }
+
+    fn from_limbs(value: Vec<u64>) -> Self {
+        let length = value.len();
+        let mut res = Self { value, length };
+        res.remove_front_zeros();
+        res
+    }
+
+    /// 取 [start, end) 范围内的 limb，超出长度的部分视为 0
+    fn limbs(&self, start: usize, end: usize) -> Self {
+        let end = cmp::min(end, self.length);
+        if start >= end {
+            return Self::with_capacity(1);
+        }
+        let mut res = self.clone_slice(start, end);
+        res.remove_front_zeros();
+        res
+    }
+
+    /// 左移整数个 limb
+    fn shl_limbs(&self, count: usize) -> Self {
+        if self.is_zero() {
+            return self.clone();
+        }
+        let mut value = vec![0; count + self.length];
+        value[count..].copy_from_slice(&self.value[0..self.length]);
+        let length = value.len();
+        Self { value, length }
+    }
+
+    pub(crate) fn remove_front_zeros(&mut self) {
         while self.length > 1 && self.value[self.length - 1] == 0 {
             self.length -= 1;
         }
@@ -135,21 +274,21 @@ impl cmp::PartialEq for BigInt {
 
 impl cmp::PartialOrd for BigInt {
     fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl cmp::Ord for BigInt {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
         if self.length != other.length {
-            return Some(cmp::Ord::cmp(&self.length, &other.length));
+            return cmp::Ord::cmp(&self.length, &other.length);
         }
         for i in (0..self.length).rev() {
             if self.value[i] != other.value[i] {
-                return Some(cmp::Ord::cmp(&self.value[i], &other.value[i]));
+                return cmp::Ord::cmp(&self.value[i], &other.value[i]);
             }
         }
-        Some(cmp::Ordering::Equal)
-    }
-}
-
-impl cmp::Ord for BigInt {
-    fn cmp(&self, other: &Self) -> cmp::Ordering {
-        cmp::PartialOrd::partial_cmp(&self, &other).unwrap()
+        cmp::Ordering::Equal
     }
 }
 
@@ -170,9 +309,9 @@ impl ops::Add<&BigInt> for &BigInt {
         for i in 0..res.length {
             let self_val = get_val(i, self.length, &self.value);
             let rhs_val = get_val(i, rhs.length, &rhs.value);
-            let sum = res.value[i] + self_val + rhs_val;
-            res.value[i + 1] = sum >> BigInt::VALUE_LEN;
-            res.value[i] = sum & BigInt::VALUE_MASK;
+            let sum = res.value[i] as u128 + self_val as u128 + rhs_val as u128;
+            res.value[i + 1] = (sum >> BigInt::VALUE_LEN) as u64;
+            res.value[i] = sum as u64;
         }
         if res.value[res.length] > 0 {
             res.length += 1;
@@ -189,21 +328,11 @@ impl ops::Sub<&BigInt> for &BigInt {
         let mut borrow: u64 = 0;
 
         for i in 0..self.length {
-            let rhs_val;
-            if i < rhs.length {
-                rhs_val = rhs.value[i];
-            } else {
-                rhs_val = 0;
-            }
-            let self_val = self.value[i];
-
-            if self_val < borrow || self_val - borrow < rhs_val {
-                res.value[i] = self_val + (1 << BigInt::VALUE_LEN) - borrow - rhs_val;
-                borrow = 1;
-            } else {
-                res.value[i] = self_val - borrow - rhs_val;
-                borrow = 0;
-            }
+            let rhs_val = if i < rhs.length { rhs.value[i] } else { 0 };
+            let (val, overflow1) = self.value[i].overflowing_sub(rhs_val);
+            let (val, overflow2) = val.overflowing_sub(borrow);
+            res.value[i] = val;
+            borrow = (overflow1 || overflow2) as u64;
         }
         res.remove_front_zeros();
         res
@@ -216,21 +345,20 @@ impl ops::Mul<&BigInt> for &BigInt {
         if self.is_zero() || rhs.is_zero() {
             return BigInt::with_capacity(1);
         }
-
-        let mut res = BigInt::with_capacity(self.length + rhs.length + 1);
-        res.length = self.length + rhs.length;
-        for i in 0..self.length {
-            for j in 0..rhs.length {
-                res.value[i + j] += self.value[i] * rhs.value[j];
-            }
-            for j in i..res.length {
-                res.value[j + 1] += res.value[j] >> BigInt::VALUE_LEN;
-                res.value[j] &= BigInt::VALUE_MASK;
-            }
+        if std::ptr::eq(self, rhs) {
+            return self.square();
         }
 
-        res.remove_front_zeros();
-        res
+        let (short, long) = if self.length < rhs.length {
+            (self.length, rhs.length)
+        } else {
+            (rhs.length, self.length)
+        };
+        if short >= BigInt::TOOM3_THRESHOLD && 3 * short > 2 * long {
+            self.mul_toom3(rhs)
+        } else {
+            self.mul_karatsuba(rhs)
+        }
     }
 }
 
@@ -245,9 +373,9 @@ impl ops::Mul<u64> for &BigInt {
         res.length = self.length;
         let mut extend: u64 = 0;
         for i in 0..self.length {
-            let val = self.value[i] * rhs + extend;
-            res.value[i] = val & BigInt::VALUE_MASK;
-            extend = val >> BigInt::VALUE_LEN;
+            let val = self.value[i] as u128 * rhs as u128 + extend as u128;
+            res.value[i] = val as u64;
+            extend = (val >> BigInt::VALUE_LEN) as u64;
         }
 
         if extend != 0 {
@@ -267,23 +395,42 @@ impl ops::Div<&BigInt> for &BigInt {
     }
 }
 
+impl ops::Div<u64> for &BigInt {
+    type Output = BigInt;
+    fn div(self, rhs: u64) -> Self::Output {
+        let (res, _) = self.div_rem_u64(rhs);
+        res
+    }
+}
+
+impl ops::Rem<u64> for &BigInt {
+    type Output = u64;
+    fn rem(self, rhs: u64) -> Self::Output {
+        let mut remain: u128 = 0;
+        for val in self.value[0..self.length].iter().rev() {
+            remain = ((remain << BigInt::VALUE_LEN) | *val as u128) % rhs as u128;
+        }
+        remain as u64
+    }
+}
+
 impl ops::Shr<u64> for &BigInt {
     type Output = BigInt;
     fn shr(self, shift: u64) -> Self::Output {
         let block_offset = (shift / BigInt::VALUE_LEN) as usize;
-        let value_offset = (shift % BigInt::VALUE_LEN) as usize;
+        let value_offset = shift % BigInt::VALUE_LEN;
+        if block_offset >= self.length {
+            return BigInt::with_capacity(1);
+        }
         let mut res = BigInt::with_capacity(self.length - block_offset + 2);
         res.length = self.length - block_offset;
 
         for i in block_offset..self.length {
-            let mut next_value = if i + 1 < self.length {
-                self.value[i + 1] & ((1 << value_offset as u64) - 1)
-            } else {
-                0
-            };
-            next_value =
-                (next_value << (BigInt::VALUE_LEN - value_offset as u64)) & BigInt::VALUE_MASK;
-            res.value[i - block_offset] = next_value + (self.value[i] >> value_offset as u64);
+            let mut val = self.value[i] >> value_offset;
+            if value_offset != 0 && i + 1 < self.length {
+                val |= self.value[i + 1] << (BigInt::VALUE_LEN - value_offset);
+            }
+            res.value[i - block_offset] = val;
         }
         res.remove_front_zeros();
         res
@@ -294,17 +441,16 @@ impl ops::Shl<u64> for &BigInt {
     type Output = BigInt;
     fn shl(self, shift: u64) -> Self::Output {
         let block_offset = (shift / BigInt::VALUE_LEN) as usize;
-        let value_offset = (shift % BigInt::VALUE_LEN) as usize;
+        let value_offset = shift % BigInt::VALUE_LEN;
         let mut res = BigInt::with_capacity(self.length + block_offset + 6);
         res.length = self.length + block_offset;
 
         for i in block_offset..self.length + block_offset {
-            let this_value = self.value[i - block_offset]
-                & ((1 << (BigInt::VALUE_LEN - value_offset as u64)) - 1);
-            let next_value =
-                self.value[i - block_offset] >> (BigInt::VALUE_LEN - value_offset as u64);
-            res.value[i] |= (this_value << value_offset) & BigInt::VALUE_MASK;
-            res.value[i + 1] |= next_value;
+            let val = self.value[i - block_offset];
+            res.value[i] |= val << value_offset;
+            if value_offset != 0 {
+                res.value[i + 1] |= val >> (BigInt::VALUE_LEN - value_offset);
+            }
         }
         while res.value[res.length] > 0 {
             res.length += 1;
@@ -314,43 +460,453 @@ impl ops::Shl<u64> for &BigInt {
     }
 }
 
+/// Knuth 算法 D，返回 `(商, 余数)`
 pub fn mod_div(x: &BigInt, y: &BigInt) -> (BigInt, BigInt) {
-    if x >= y {
-        let mut res = BigInt::with_capacity(x.length);
-        let mut remain = x.clone_slice(x.length - y.length + 1, x.length);
-
-        for i in (0..x.length - y.length + 1).rev() {
-            let mut lower: u64 = 0;
-            let mut upper = BigInt::VALUE_MASK;
-
-            res.value[i] = 0;
-            remain = &remain << BigInt::VALUE_LEN;
-            remain.value[0] = x.value[i];
-
-            while lower <= upper {
-                let mid = (lower + upper) >> 1;
-                if y * mid <= remain {
-                    res.value[i] = mid;
-                    lower = mid + 1;
-                } else {
-                    upper = mid - 1;
-                }
+    if x < y {
+        return (BigInt::with_capacity(1), x.clone());
+    }
+    if y.length == 1 {
+        let (q, r) = x.div_rem_u64(y.value[0]);
+        return (q, BigInt::from_slice(&[r]));
+    }
+
+    // 规格化：左移使除数最高 limb 的最高位为 1，保证试商最多偏大 2
+    let shift = y.value[y.length - 1].leading_zeros() as u64;
+    let v = (y << shift).value;
+    let mut u = vec![0; x.length + 1];
+    let shifted = x << shift;
+    u[0..shifted.length].copy_from_slice(&shifted.value[0..shifted.length]);
+
+    let n = y.length;
+    let base: u128 = 1 << BigInt::VALUE_LEN;
+    let mut res = BigInt::with_capacity(x.length - n + 1);
+    for j in (0..=x.length - n).rev() {
+        // 用被除数最高两个 limb 除以除数最高 limb 估计商，再用次高 limb 修正
+        let num = ((u[j + n] as u128) << BigInt::VALUE_LEN) | u[j + n - 1] as u128;
+        let mut qhat = num / v[n - 1] as u128;
+        let mut rhat = num % v[n - 1] as u128;
+        while qhat >= base
+            || qhat * v[n - 2] as u128 > ((rhat << BigInt::VALUE_LEN) | u[j + n - 2] as u128)
+        {
+            qhat -= 1;
+            rhat += v[n - 1] as u128;
+            if rhat >= base {
+                break;
             }
-            remain = &remain - &(y * res.value[i]);
         }
 
-        res.length = x.length;
-        res.remove_front_zeros();
-        (res, remain)
-    } else {
-        (BigInt::with_capacity(1), x.clone())
+        // u[j..=j+n] -= qhat * v
+        let mut carry: u128 = 0;
+        let mut borrow: u64 = 0;
+        for i in 0..n {
+            let prod = qhat * v[i] as u128 + carry;
+            carry = prod >> BigInt::VALUE_LEN;
+            let (val, overflow1) = u[i + j].overflowing_sub(prod as u64);
+            let (val, overflow2) = val.overflowing_sub(borrow);
+            u[i + j] = val;
+            borrow = (overflow1 || overflow2) as u64;
+        }
+        let (val, overflow1) = u[j + n].overflowing_sub(carry as u64);
+        let (val, overflow2) = val.overflowing_sub(borrow);
+        u[j + n] = val;
+
+        // 减成负数说明试商仍大了 1，加回一次除数
+        if overflow1 || overflow2 {
+            qhat -= 1;
+            let mut carry: u128 = 0;
+            for i in 0..n {
+                let sum = u[i + j] as u128 + v[i] as u128 + carry;
+                u[i + j] = sum as u64;
+                carry = sum >> BigInt::VALUE_LEN;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u64);
+        }
+        res.value[j] = qhat as u64;
+    }
+
+    res.length = x.length - n + 1;
+    res.remove_front_zeros();
+    let remain = &BigInt::from_limbs(u[0..n].to_vec()) >> shift;
+    (res, remain)
+}
+
+/// Toom-3 插值过程中需要的带符号大整数
+struct Signed {
+    neg: bool,
+    mag: BigInt,
+}
+
+impl From<BigInt> for Signed {
+    fn from(mag: BigInt) -> Self {
+        Self { neg: false, mag }
+    }
+}
+
+impl Signed {
+    fn new(neg: bool, mag: BigInt) -> Self {
+        let neg = neg && !mag.is_zero();
+        Self { neg, mag }
+    }
+
+    fn add(&self, rhs: &Self) -> Self {
+        if self.neg == rhs.neg {
+            Self::new(self.neg, &self.mag + &rhs.mag)
+        } else if self.mag >= rhs.mag {
+            Self::new(self.neg, &self.mag - &rhs.mag)
+        } else {
+            Self::new(rhs.neg, &rhs.mag - &self.mag)
+        }
+    }
+
+    fn sub(&self, rhs: &Self) -> Self {
+        self.add(&Self {
+            neg: !rhs.neg,
+            mag: rhs.mag.clone(),
+        })
+    }
+
+    fn mul(&self, rhs: &Self) -> Self {
+        Self::new(self.neg != rhs.neg, &self.mag * &rhs.mag)
+    }
+
+    fn double(&self) -> Self {
+        Self::new(self.neg, &self.mag << 1)
+    }
+
+    /// 已知能整除时的除法
+    fn div_exact(&self, divisor: u64) -> Self {
+        Self::new(self.neg, &self.mag / divisor)
+    }
+}
+
+/// acc += x，进位一直传播到 acc 的末尾
+fn add_into(acc: &mut [u64], x: &[u64]) {
+    let mut carry: u64 = 0;
+    for (i, a) in acc.iter_mut().enumerate() {
+        if i >= x.len() && carry == 0 {
+            break;
+        }
+        let val = *a as u128 + if i < x.len() { x[i] as u128 } else { 0 } + carry as u128;
+        *a = val as u64;
+        carry = (val >> BigInt::VALUE_LEN) as u64;
+    }
+}
+
+/// acc -= x，要求 acc >= x
+fn sub_into(acc: &mut [u64], x: &[u64]) {
+    let mut borrow: u64 = 0;
+    for (i, a) in acc.iter_mut().enumerate() {
+        if i >= x.len() && borrow == 0 {
+            break;
+        }
+        let (val, overflow1) = a.overflowing_sub(if i < x.len() { x[i] } else { 0 });
+        let (val, overflow2) = val.overflowing_sub(borrow);
+        *a = val;
+        borrow = (overflow1 || overflow2) as u64;
+    }
+}
+
+fn add_slices(a: &[u64], b: &[u64]) -> Vec<u64> {
+    let (short, long) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    let mut res = Vec::with_capacity(long.len() + 1);
+    res.extend_from_slice(long);
+    res.push(0);
+    add_into(&mut res, short);
+    res
+}
+
+/// out = a * b，要求 out 的长度为 a.len() + b.len() 且初始为 0
+fn mul_slice(a: &[u64], b: &[u64], out: &mut [u64]) {
+    let (short, long) = if a.len() < b.len() { (a, b) } else { (b, a) };
+    if short.len() < BigInt::KARATSUBA_THRESHOLD {
+        return mul_schoolbook_slice(long, short, out);
+    }
+
+    let m = long.len().div_ceil(2);
+    let (x0, x1) = long.split_at(m);
+    if short.len() <= m {
+        // 长度相差悬殊时把长的一方拆开分别相乘
+        mul_slice(x0, short, &mut out[0..m + short.len()]);
+        let mut high = vec![0; x1.len() + short.len()];
+        mul_slice(x1, short, &mut high);
+        add_into(&mut out[m..], &high);
+        return;
+    }
+
+    // (x1 * B^m + x0)(y1 * B^m + y0) = z2 * B^2m + z1 * B^m + z0
+    let (y0, y1) = short.split_at(m);
+    let (low, high) = out.split_at_mut(2 * m);
+    mul_slice(x0, y0, low);
+    mul_slice(x1, y1, high);
+
+    let sum_x = add_slices(x0, x1);
+    let sum_y = add_slices(y0, y1);
+    let mut z1 = vec![0; sum_x.len() + sum_y.len()];
+    mul_slice(&sum_x, &sum_y, &mut z1);
+    sub_into(&mut z1, low);
+    sub_into(&mut z1, high);
+
+    // z1 的高位必然为 0，截断到 out 的剩余长度
+    let len = cmp::min(z1.len(), out.len() - m);
+    add_into(&mut out[m..], &z1[0..len]);
+}
+
+fn mul_schoolbook_slice(a: &[u64], b: &[u64], out: &mut [u64]) {
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry: u64 = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let val = out[i + j] as u128 + x as u128 * y as u128 + carry as u128;
+            out[i + j] = val as u64;
+            carry = (val >> BigInt::VALUE_LEN) as u64;
+        }
+        out[i + b.len()] = carry;
     }
 }
 
+/// out = a^2，要求 out 的长度为 2 * a.len() 且初始为 0
+fn square_slice(a: &[u64], out: &mut [u64]) {
+    if a.len() < BigInt::KARATSUBA_THRESHOLD {
+        return square_schoolbook_slice(a, out);
+    }
+
+    // (x1 * B^m + x0)^2 = z2 * B^2m + ((x0 + x1)^2 - z0 - z2) * B^m + z0
+    let m = a.len().div_ceil(2);
+    let (x0, x1) = a.split_at(m);
+    let (low, high) = out.split_at_mut(2 * m);
+    square_slice(x0, low);
+    square_slice(x1, high);
+
+    let sum = add_slices(x0, x1);
+    let mut z1 = vec![0; 2 * sum.len()];
+    square_slice(&sum, &mut z1);
+    sub_into(&mut z1, low);
+    sub_into(&mut z1, high);
+
+    let len = cmp::min(z1.len(), out.len() - m);
+    add_into(&mut out[m..], &z1[0..len]);
+}
+
+fn square_schoolbook_slice(a: &[u64], out: &mut [u64]) {
+    // 先累加 i < j 的交叉项
+    for i in 0..a.len() {
+        let mut carry: u64 = 0;
+        for j in i + 1..a.len() {
+            let val = out[i + j] as u128 + a[i] as u128 * a[j] as u128 + carry as u128;
+            out[i + j] = val as u64;
+            carry = (val >> BigInt::VALUE_LEN) as u64;
+        }
+        out[i + a.len()] = carry;
+    }
+    // 交叉项乘 2，再加上对角线上的平方项
+    let mut carry: u128 = 0;
+    for i in 0..a.len() {
+        let sq = a[i] as u128 * a[i] as u128;
+        let lo = ((out[2 * i] as u128) << 1) + (sq as u64 as u128) + carry;
+        out[2 * i] = lo as u64;
+        let hi =
+            ((out[2 * i + 1] as u128) << 1) + (sq >> BigInt::VALUE_LEN) + (lo >> BigInt::VALUE_LEN);
+        out[2 * i + 1] = hi as u64;
+        carry = hi >> BigInt::VALUE_LEN;
+    }
+}
 
 lazy_static! {
+    pub static ref ZERO: BigInt = BigInt::from_slice(&[0]);
     pub static ref ONE: BigInt = BigInt::from_slice(&[1]);
     pub static ref TWO: BigInt = BigInt::from_slice(&[2]);
     pub static ref THREE: BigInt = BigInt::from_slice(&[3]);
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 每个 limb 都是 `u64::MAX`，进位最多
+    fn all_ones(length: usize) -> BigInt {
+        BigInt::from_slice(&vec![u64::MAX; length])
+    }
+
+    fn check_mul(a: &BigInt, b: &BigInt) {
+        let expected = a.mul_schoolbook(b);
+        assert_eq!(
+            a.mul_karatsuba(b),
+            expected,
+            "karatsuba {} x {}",
+            a.length,
+            b.length
+        );
+        assert_eq!(
+            a.mul_toom3(b),
+            expected,
+            "toom3 {} x {}",
+            a.length,
+            b.length
+        );
+        assert_eq!(a * b, expected, "mul {} x {}", a.length, b.length);
+    }
+
+    fn check_square(a: &BigInt) {
+        assert_eq!(a.square(), a.mul_schoolbook(a), "square {}", a.length);
+    }
+
+    #[test]
+    fn mul_around_karatsuba_threshold() {
+        let t = BigInt::KARATSUBA_THRESHOLD;
+        for len in [
+            1,
+            2,
+            t - 1,
+            t,
+            t + 1,
+            2 * t - 1,
+            2 * t,
+            2 * t + 1,
+            5 * t + 3,
+        ] {
+            check_mul(&BigInt::rand(len), &BigInt::rand(len));
+            check_mul(&all_ones(len), &all_ones(len));
+        }
+    }
+
+    #[test]
+    fn mul_around_toom3_threshold() {
+        let t = BigInt::TOOM3_THRESHOLD;
+        for len in [t - 1, t, t + 1] {
+            check_mul(&BigInt::rand(len), &BigInt::rand(len));
+        }
+        check_mul(&all_ones(t), &all_ones(t));
+    }
+
+    #[test]
+    fn mul_unbalanced() {
+        let k = BigInt::KARATSUBA_THRESHOLD;
+        let t = BigInt::TOOM3_THRESHOLD;
+        for (a, b) in [
+            (1, 3 * k),
+            (k - 1, k + 1),
+            (k, 4 * k + 5),
+            (3 * k + 1, 10 * k),
+            (t / 2, t + 3),
+            (2 * t / 3 + 1, t),
+            (t, t + t / 2 - 1),
+        ] {
+            let (a, b) = (BigInt::rand(a), BigInt::rand(b));
+            check_mul(&a, &b);
+            check_mul(&b, &a);
+        }
+    }
+
+    #[test]
+    fn mul_by_zero() {
+        let zero = BigInt::from_slice(&[0]);
+        let a = BigInt::rand(3 * BigInt::KARATSUBA_THRESHOLD);
+        assert!((&a * &zero).is_zero());
+        assert!((&zero * &a).is_zero());
+        assert!(zero.square().is_zero());
+    }
+
+    /// 商和余数满足 `q * b + r == a` 且 `r < b`
+    fn check_div(a: &BigInt, b: &BigInt) {
+        let (q, r) = mod_div(a, b);
+        assert!(r < *b, "remainder {} / {}", a.length, b.length);
+        assert_eq!(&(&q * b) + &r, *a, "quotient {} / {}", a.length, b.length);
+    }
+
+    /// 随机 limb 中混入 0、全 1 和最高位附近的值，容易触发试商的修正
+    fn rand_limbs(length: usize) -> BigInt {
+        let mut rng = rand::thread_rng();
+        let limbs = (0..length)
+            .map(|_| match rng.gen_range(0..6) {
+                0 => 0,
+                1 => u64::MAX,
+                2 => 1 << 63,
+                3 => (1 << 63) - 1,
+                4 => 1,
+                _ => rng.gen(),
+            })
+            .collect::<Vec<_>>();
+        let mut x = BigInt::from_slice(&limbs);
+        x.remove_front_zeros();
+        x
+    }
+
+    #[test]
+    fn div_random() {
+        for _ in 0..200 {
+            for a_len in 1..8 {
+                for b_len in 1..=a_len {
+                    let b = rand_limbs(b_len);
+                    if !b.is_zero() {
+                        check_div(&rand_limbs(a_len), &b);
+                    }
+                }
+            }
+        }
+        for (a_len, b_len) in [(64, 32), (65, 33), (128, 3), (100, 99)] {
+            check_div(&BigInt::rand(a_len), &BigInt::rand(b_len));
+            check_div(&all_ones(a_len), &all_ones(b_len));
+        }
+    }
+
+    #[test]
+    fn div_exact_multiples() {
+        for (q_len, b_len) in [(1, 2), (3, 2), (4, 4), (8, 5)] {
+            let b = rand_limbs(b_len);
+            if b.is_zero() {
+                continue;
+            }
+            let q = BigInt::rand(q_len);
+            let a = &q * &b;
+            assert_eq!(mod_div(&a, &b), (q.clone(), BigInt::from_slice(&[0])));
+            // 比倍数少 1 时商减 1，余数为 b - 1
+            let (q1, r1) = mod_div(&(&a - &ONE), &b);
+            assert_eq!(&q1 + &ONE, q);
+            assert_eq!(&r1 + &ONE, b);
+        }
+    }
+
+    #[test]
+    fn div_single_limb() {
+        for d in [1, 2, 3, 10, (1 << 63) - 1, 1 << 63, u64::MAX] {
+            let b = BigInt::from_slice(&[d]);
+            for len in [1, 2, 5, 33] {
+                check_div(&BigInt::rand(len), &b);
+                check_div(&all_ones(len), &b);
+            }
+        }
+    }
+
+    #[test]
+    fn div_by_larger() {
+        let a = BigInt::rand(3);
+        let b = BigInt::rand(4);
+        assert_eq!(mod_div(&a, &b), (BigInt::from_slice(&[0]), a.clone()));
+        assert_eq!(mod_div(&a, &a), (ONE.clone(), BigInt::from_slice(&[0])));
+        let zero = BigInt::from_slice(&[0]);
+        assert_eq!(mod_div(&zero, &b), (zero.clone(), zero));
+    }
+
+    #[test]
+    fn div_add_back() {
+        // Hacker's Delight 中触发加回一步的用例，limb 换成 64 位。
+        // 除数只有两个 limb 时试商的修正已经精确，不会走到加回
+        let half = 1 << 63;
+        for (u, v) in [
+            (vec![0, 0, half, half - 1], vec![1, 0, half]),
+            (vec![3, 0, half, half - 1], vec![1, 0, half]),
+            (vec![0, 0, 0, half, half - 1], vec![1, 0, 0, half]),
+        ] {
+            check_div(&BigInt::from_slice(&u), &BigInt::from_slice(&v));
+        }
+    }
+
+    #[test]
+    fn square_matches_mul() {
+        let k = BigInt::KARATSUBA_THRESHOLD;
+        let t = BigInt::TOOM3_THRESHOLD;
+        for len in [1, 2, k - 1, k, k + 1, 2 * k + 1, t - 1, t, t + 1] {
+            check_square(&BigInt::rand(len));
+            check_square(&all_ones(len));
+        }
+    }
+}
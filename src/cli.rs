@@ -0,0 +1,120 @@
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufReader;
+
+use crate::hash::HashAlgorithm;
+use crate::merkle::{self, MerkleSignature, MerkleTree};
+use crate::rsa::{RsaPrivateKey, RsaPublicKey};
+
+const USAGE: &str = "\
+Usage:
+    rsa-rust-cpp                      start the HTTP server
+    rsa-rust-cpp merkle-sign <file> <public key file> <private key file> [chunk size]
+    rsa-rust-cpp merkle-proof <file> <signature file> <chunk index>
+    rsa-rust-cpp merkle-verify <file> <signature file> <public key file>
+    rsa-rust-cpp merkle-verify-chunk <chunk file> <signature file> <public key file> <chunk index> <proof file>
+
+Private keys may be in any format accepted by the API, encrypted PKCS#8 keys
+read their passphrase from RSA_KEY_PASSPHRASE.";
+
+fn read_to_string(path: &str) -> Result<String, Box<dyn Error>> {
+    Ok(fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?)
+}
+
+fn open(path: &str) -> Result<BufReader<File>, Box<dyn Error>> {
+    Ok(File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("{path}: {e}"))?)
+}
+
+fn parse_index(s: &str) -> Result<usize, Box<dyn Error>> {
+    Ok(s.parse().map_err(|_| format!("Invalid chunk index: {s}"))?)
+}
+
+fn read_public_key(path: &str) -> Result<RsaPublicKey, Box<dyn Error>> {
+    Ok(RsaPublicKey::parse(&read_to_string(path)?)?)
+}
+
+fn read_signature(path: &str) -> Result<MerkleSignature, Box<dyn Error>> {
+    serde_json::from_str(&read_to_string(path)?).map_err(|_| "Invalid Merkle signature".into())
+}
+
+fn merkle_sign(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let [file, public_key, private_key, rest @ ..] = args else {
+        return Err(USAGE.into());
+    };
+    let chunk_size = match rest {
+        [] => merkle::DEFAULT_CHUNK_SIZE,
+        [size] => size
+            .parse()
+            .map_err(|_| format!("Invalid chunk size: {size}"))?,
+        _ => return Err(USAGE.into()),
+    };
+    let passphrase = env::var("RSA_KEY_PASSPHRASE").ok();
+    let private_key = RsaPrivateKey::parse(
+        read_public_key(public_key)?,
+        &read_to_string(private_key)?,
+        passphrase.as_deref(),
+    )?;
+    let tree = MerkleTree::from_reader(open(file)?, chunk_size, HashAlgorithm::Sha256)?;
+    let signature = tree.sign(&private_key)?;
+    Ok(serde_json::to_string_pretty(&signature).unwrap())
+}
+
+fn merkle_proof(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let [file, signature, index] = args else {
+        return Err(USAGE.into());
+    };
+    let signature = read_signature(signature)?;
+    let tree = MerkleTree::from_reader(open(file)?, signature.chunk_size, signature.hash)?;
+    let proof = tree.proof(parse_index(index)?)?;
+    Ok(serde_json::to_string_pretty(&proof).unwrap())
+}
+
+fn merkle_verify(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let [file, signature, public_key] = args else {
+        return Err(USAGE.into());
+    };
+    let signature = read_signature(signature)?;
+    let verified = signature.verify_data(&read_public_key(public_key)?, open(file)?)?;
+    Ok(verified.to_string())
+}
+
+fn merkle_verify_chunk(args: &[String]) -> Result<String, Box<dyn Error>> {
+    let [chunk, signature, public_key, index, proof] = args else {
+        return Err(USAGE.into());
+    };
+    let chunk = fs::read(chunk).map_err(|e| format!("{chunk}: {e}"))?;
+    let proof: Vec<String> =
+        serde_json::from_str(&read_to_string(proof)?).map_err(|_| "Invalid Merkle proof")?;
+    let verified = read_signature(signature)?.verify_chunk(
+        &read_public_key(public_key)?,
+        parse_index(index)?,
+        &chunk,
+        &proof,
+    )?;
+    Ok(verified.to_string())
+}
+
+/// 处理命令行子命令，返回进程退出码，验证失败时为 1，出错时为 2
+pub fn run(args: &[String]) -> i32 {
+    let (command, args) = args.split_first().unwrap();
+    let res = match command.as_str() {
+        "merkle-sign" => merkle_sign(args),
+        "merkle-proof" => merkle_proof(args),
+        "merkle-verify" => merkle_verify(args),
+        "merkle-verify-chunk" => merkle_verify_chunk(args),
+        _ => Err(USAGE.into()),
+    };
+    match res {
+        Ok(output) => {
+            println!("{output}");
+            (output == "false") as i32
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            2
+        }
+    }
+}
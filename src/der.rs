@@ -0,0 +1,157 @@
+use crate::bigint::BigInt;
+use crate::error::Error;
+
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_SEQUENCE: u8 = 0x30;
+
+/// 编码一个 TLV，长度超过 127 时使用长格式
+pub fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut res = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        res.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        res.push(0x80 | (bytes.len() - skip) as u8);
+        res.extend_from_slice(&bytes[skip..]);
+    }
+    res.extend_from_slice(content);
+    res
+}
+
+pub fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    encode(TAG_SEQUENCE, &items.concat())
+}
+
+/// 非负整数，最高位为 1 时在前面补 0
+pub fn integer(x: &BigInt) -> Vec<u8> {
+    let mut bytes = x.to_bytes_be(1);
+    if bytes[0] & 0x80 != 0 {
+        bytes.insert(0, 0);
+    }
+    encode(TAG_INTEGER, &bytes)
+}
+
+pub fn bit_string(data: &[u8]) -> Vec<u8> {
+    let mut content = vec![0];
+    content.extend_from_slice(data);
+    encode(TAG_BIT_STRING, &content)
+}
+
+pub fn octet_string(data: &[u8]) -> Vec<u8> {
+    encode(TAG_OCTET_STRING, data)
+}
+
+pub fn null() -> Vec<u8> {
+    encode(TAG_NULL, &[])
+}
+
+/// `oid` 为已编码好的 OID 内容
+pub fn oid(oid: &[u8]) -> Vec<u8> {
+    encode(TAG_OID, oid)
+}
+
+/// 顺序读取 DER 数据
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// 下一个元素的标签，用于处理可选字段
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().copied()
+    }
+
+    /// 读取一个 TLV 并返回其内容，标签不符时返回错误
+    pub fn read(&mut self, tag: u8) -> Result<&'a [u8], Error> {
+        if self.data.len() < 2 {
+            return Err(Error::Parse("Unexpected end of DER data"));
+        }
+        if self.data[0] != tag {
+            return Err(Error::Parse("Unexpected DER tag"));
+        }
+        let (len, header) = match self.data[1] {
+            len @ 0..=0x7f => (len as usize, 2),
+            0x81..=0x84 => {
+                let n = (self.data[1] & 0x7f) as usize;
+                let bytes = self
+                    .data
+                    .get(2..2 + n)
+                    .ok_or(Error::Parse("Unexpected end of DER data"))?;
+                if bytes[0] == 0 {
+                    return Err(Error::Parse("Invalid DER length"));
+                }
+                let len = bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize);
+                if len < 0x80 {
+                    return Err(Error::Parse("Invalid DER length"));
+                }
+                (len, 2 + n)
+            }
+            _ => return Err(Error::Parse("Invalid DER length")),
+        };
+        let content = self
+            .data
+            .get(header..header + len)
+            .ok_or(Error::Parse("Unexpected end of DER data"))?;
+        self.data = &self.data[header + len..];
+        Ok(content)
+    }
+
+    pub fn read_sequence(&mut self) -> Result<Reader<'a>, Error> {
+        Ok(Reader::new(self.read(TAG_SEQUENCE)?))
+    }
+
+    /// 只接受非负整数
+    pub fn read_integer(&mut self) -> Result<BigInt, Error> {
+        let bytes = self.read(TAG_INTEGER)?;
+        match bytes.first() {
+            None => Err(Error::Parse("Invalid DER integer")),
+            Some(b) if b & 0x80 != 0 => Err(Error::Parse("Negative DER integer")),
+            Some(_) => Ok(BigInt::from_bytes_be(bytes)),
+        }
+    }
+
+    pub fn read_bit_string(&mut self) -> Result<&'a [u8], Error> {
+        match self.read(TAG_BIT_STRING)? {
+            [0, data @ ..] => Ok(data),
+            _ => Err(Error::Unsupported("Unsupported DER bit string")),
+        }
+    }
+
+    pub fn read_octet_string(&mut self) -> Result<&'a [u8], Error> {
+        self.read(TAG_OCTET_STRING)
+    }
+
+    pub fn read_oid(&mut self) -> Result<&'a [u8], Error> {
+        self.read(TAG_OID)
+    }
+
+    pub fn read_null(&mut self) -> Result<(), Error> {
+        match self.read(TAG_NULL)? {
+            [] => Ok(()),
+            _ => Err(Error::Parse("Invalid DER null")),
+        }
+    }
+
+    /// 确认数据已全部读完
+    pub fn finish(self) -> Result<(), Error> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Parse("Trailing DER data"))
+        }
+    }
+}
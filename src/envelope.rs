@@ -0,0 +1,154 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rand::Rng;
+
+use crate::error::Error;
+use crate::rsa::{self, Padding, RsaPrivateKey, RsaPublicKey};
+
+/// 当前的信封格式版本
+const VERSION: u8 = 1;
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// 包装对称密钥的方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum KeyWrap {
+    #[default]
+    #[serde(rename = "rsa-oaep-sha256")]
+    RsaOaepSha256,
+}
+
+/// 加密数据使用的 AEAD 算法
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum AeadAlgorithm {
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+}
+
+impl KeyWrap {
+    /// 与序列化后的名称相同，用于计算附加数据
+    fn name(&self) -> &'static str {
+        match self {
+            Self::RsaOaepSha256 => "rsa-oaep-sha256",
+        }
+    }
+}
+
+impl AeadAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Aes256Gcm => "aes-256-gcm",
+        }
+    }
+}
+
+/// 混合加密的结果，自带解密所需的全部参数
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Envelope {
+    pub version: u8,
+    pub key_wrap: KeyWrap,
+    pub aead: AeadAlgorithm,
+    /// 被 RSA 加密的对称密钥，只有一个分块，格式与 `rsa::encrypt_bytes` 的输出相同
+    pub wrapped_key: String,
+    /// base64
+    pub nonce: String,
+    /// base64，末尾带认证标签
+    pub ciphertext: String,
+}
+
+impl Envelope {
+    /// 作为 AEAD 附加数据的头部，防止算法字段被篡改
+    fn aad(&self) -> Vec<u8> {
+        format!(
+            "{}:{}:{}",
+            self.version,
+            self.key_wrap.name(),
+            self.aead.name()
+        )
+        .into_bytes()
+    }
+
+    /// 由 `ciphertext` 的长度推算出的明文长度，用于在解密前限制输入大小
+    pub fn message_len(&self) -> usize {
+        let padding = self
+            .ciphertext
+            .bytes()
+            .rev()
+            .take_while(|&b| b == b'=')
+            .count();
+        (self.ciphertext.len() / 4 * 3)
+            .saturating_sub(padding)
+            .saturating_sub(TAG_LEN)
+    }
+}
+
+/// 用随机生成的 AES-256 密钥加密 `message`，再用 RSA-OAEP 包装该密钥
+pub fn seal(message: &[u8], key: &RsaPublicKey) -> Result<Envelope, Error> {
+    // 对称密钥必须放进一个 OAEP 分块，否则各分块可以被单独替换
+    if rsa::block_count(KEY_LEN, key, Padding::Oaep)? != 1 {
+        return Err(Error::InvalidKey("Modulus too small to wrap the data key"));
+    }
+    let mut rng = rand::thread_rng();
+    let mut data_key = [0u8; KEY_LEN];
+    rng.fill(&mut data_key);
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut nonce);
+
+    let mut envelope = Envelope {
+        version: VERSION,
+        key_wrap: KeyWrap::RsaOaepSha256,
+        aead: AeadAlgorithm::Aes256Gcm,
+        wrapped_key: rsa::encrypt_bytes(&data_key, key, Padding::Oaep, b"")?,
+        nonce: BASE64.encode(nonce),
+        ciphertext: String::new(),
+    };
+    let cipher =
+        Aes256Gcm::new_from_slice(&data_key).map_err(|_| Error::Decryption("Invalid data key"))?;
+    let aad = envelope.aad();
+    let ciphertext = cipher
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: message,
+                aad: &aad,
+            },
+        )
+        .map_err(|_| Error::Internal("Encryption failed"))?;
+    envelope.ciphertext = BASE64.encode(ciphertext);
+    Ok(envelope)
+}
+
+/// `seal` 的逆过程，密钥或数据被篡改时返回错误
+pub fn open(envelope: &Envelope, key: &RsaPrivateKey) -> Result<Vec<u8>, Error> {
+    if envelope.version != VERSION {
+        return Err(Error::Unsupported("Unsupported envelope version"));
+    }
+    if envelope.wrapped_key.contains(',') {
+        return Err(Error::Parse("Malformed envelope"));
+    }
+    let data_key = rsa::decrypt_bytes(&envelope.wrapped_key, key, Padding::Oaep, b"")?;
+    let nonce = BASE64
+        .decode(&envelope.nonce)
+        .map_err(|_| Error::Parse("Invalid base64"))?;
+    if data_key.len() != KEY_LEN || nonce.len() != NONCE_LEN {
+        return Err(Error::Parse("Malformed envelope"));
+    }
+    let ciphertext = BASE64
+        .decode(&envelope.ciphertext)
+        .map_err(|_| Error::Parse("Invalid base64"))?;
+
+    let cipher =
+        Aes256Gcm::new_from_slice(&data_key).map_err(|_| Error::Decryption("Invalid data key"))?;
+    cipher
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: &envelope.aad(),
+            },
+        )
+        .map_err(|_| Error::Decryption("Envelope authentication failed"))
+}
@@ -0,0 +1,74 @@
+use std::fmt;
+
+/// crate 内统一使用的错误类型，附带的字符串为给用户看的说明
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// 十六进制串格式错误
+    InvalidHex(&'static str),
+    /// base64、DER、PEM、JWK、OpenSSH 等编码无法解析
+    Parse(&'static str),
+    /// 密钥参数不合法，或公私钥不匹配
+    InvalidKey(&'static str),
+    /// 其他不合法的参数
+    InvalidInput(&'static str),
+    /// 消息超过密钥与填充方式允许的长度
+    MessageTooLong,
+    /// 编码消息时填充失败
+    Padding(&'static str),
+    /// 解密失败、口令错误或数据被篡改
+    Decryption(&'static str),
+    /// 不支持的算法、格式或版本
+    Unsupported(&'static str),
+    NotFound(&'static str),
+    /// 操作被用户取消
+    Cancelled,
+    /// 服务器繁忙，稍后再试
+    Busy(&'static str),
+    /// 读写文件等与输入无关的错误
+    Internal(&'static str),
+}
+
+impl Error {
+    /// 机器可读的错误码
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidHex(_) => "invalid_hex",
+            Self::Parse(_) => "parse_error",
+            Self::InvalidKey(_) => "invalid_key",
+            Self::InvalidInput(_) => "invalid_input",
+            Self::MessageTooLong => "message_too_long",
+            Self::Padding(_) => "padding_error",
+            Self::Decryption(_) => "decryption_error",
+            Self::Unsupported(_) => "unsupported",
+            Self::NotFound(_) => "not_found",
+            Self::Cancelled => "cancelled",
+            Self::Busy(_) => "busy",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::MessageTooLong => "Message too long",
+            Self::Cancelled => "Cancelled",
+            Self::InvalidHex(message)
+            | Self::Parse(message)
+            | Self::InvalidKey(message)
+            | Self::InvalidInput(message)
+            | Self::Padding(message)
+            | Self::Decryption(message)
+            | Self::Unsupported(message)
+            | Self::NotFound(message)
+            | Self::Busy(message)
+            | Self::Internal(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for Error {}
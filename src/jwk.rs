@@ -0,0 +1,126 @@
+use std::fmt;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64URL, Engine};
+
+use crate::bigint::BigInt;
+use crate::error::Error;
+use crate::hash::HashAlgorithm;
+use crate::rsa::{RsaPrivateKey, RsaPublicKey};
+
+/// RFC 7517 JSON Web Key，私钥字段只在私钥中出现
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Jwk {
+    pub kty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+    pub n: String,
+    pub e: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub d: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub p: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dq: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub qi: Option<String>,
+}
+
+fn encode_int(x: &BigInt) -> String {
+    BASE64URL.encode(x.to_bytes_be(1))
+}
+
+fn decode_int(s: &str) -> Result<BigInt, Error> {
+    let bytes = BASE64URL
+        .decode(s)
+        .map_err(|_| Error::Parse("Invalid base64url in JWK"))?;
+    Ok(BigInt::from_bytes_be(&bytes))
+}
+
+impl Jwk {
+    pub fn with_alg(mut self, alg: Option<&str>) -> Self {
+        self.alg = alg.map(String::from);
+        self
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|_| Error::Parse("Invalid JWK"))
+    }
+
+    fn check_kty(&self) -> Result<(), Error> {
+        if self.kty != "RSA" {
+            return Err(Error::Unsupported("JWK is not an RSA key"));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Jwk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&serde_json::to_string(self).map_err(|_| fmt::Error)?)
+    }
+}
+
+impl RsaPublicKey {
+    /// RFC 7638 指纹，用作默认的 `kid`
+    pub fn jwk_thumbprint(&self) -> String {
+        let json = format!(
+            r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#,
+            encode_int(&self.e),
+            encode_int(&self.n)
+        );
+        BASE64URL.encode(HashAlgorithm::Sha256.digest(json.as_bytes()))
+    }
+
+    pub fn to_jwk(&self) -> Jwk {
+        Jwk {
+            kty: "RSA".to_owned(),
+            kid: Some(self.jwk_thumbprint()),
+            n: encode_int(&self.n),
+            e: encode_int(&self.e),
+            ..Default::default()
+        }
+    }
+
+    /// 忽略其中的私钥字段
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self, Error> {
+        jwk.check_kty()?;
+        Self::new(decode_int(&jwk.n)?, decode_int(&jwk.e)?)
+    }
+}
+
+impl RsaPrivateKey {
+    pub fn to_jwk(&self) -> Jwk {
+        let mut jwk = self.public_key().to_jwk();
+        jwk.d = Some(encode_int(&self.d));
+        if let Some(crt) = &self.crt {
+            jwk.p = Some(encode_int(&crt.p));
+            jwk.q = Some(encode_int(&crt.q));
+            jwk.dp = Some(encode_int(&crt.dp));
+            jwk.dq = Some(encode_int(&crt.dq));
+            jwk.qi = Some(encode_int(&crt.qinv));
+        }
+        jwk
+    }
+
+    /// 带有 p、q 时 CRT 参数根据 p、q 重新计算
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self, Error> {
+        let public_key = RsaPublicKey::from_jwk(jwk)?;
+        let d = decode_int(
+            jwk.d
+                .as_deref()
+                .ok_or(Error::Parse("JWK has no private exponent"))?,
+        )?;
+        match (&jwk.p, &jwk.q) {
+            (Some(p), Some(q)) => Self::new(public_key.n, public_key.e, d)?
+                .with_primes(&decode_int(p)?, &decode_int(q)?),
+            (None, None) => Self::new(public_key.n, public_key.e, d),
+            _ => Err(Error::Parse("JWK must contain both p and q")),
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use std::str::FromStr;
+
+use crate::bigint::ZERO;
+use crate::der::{self, Reader};
+use crate::error::Error;
+use crate::jwk::Jwk;
+use crate::openssh;
+use crate::pbes2;
+use crate::pem;
+use crate::rsa::{RsaPrivateKey, RsaPublicKey};
+
+/// rsaEncryption，即 1.2.840.113549.1.1.1
+const RSA_ENCRYPTION_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+const PKCS1_PUBLIC_LABEL: &str = "RSA PUBLIC KEY";
+const PKCS1_PRIVATE_LABEL: &str = "RSA PRIVATE KEY";
+const SPKI_LABEL: &str = "PUBLIC KEY";
+const PKCS8_LABEL: &str = "PRIVATE KEY";
+const ENCRYPTED_PKCS8_LABEL: &str = "ENCRYPTED PRIVATE KEY";
+
+/// 密钥的导出格式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// `BigInt::fmt_hex` 拼接的十六进制
+    #[default]
+    Hex,
+    /// PKCS#1 PEM
+    Pkcs1,
+    /// 公钥为 SPKI PEM，私钥为 PKCS#8 PEM
+    Pkcs8,
+    /// JSON 格式的 JWK
+    Jwk,
+    /// 公钥为 `ssh-rsa AAAA...`，私钥为 OpenSSH 可以直接读取的 PKCS#1 PEM
+    OpenSsh,
+}
+
+impl FromStr for KeyFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hex" => Ok(Self::Hex),
+            "pkcs1" => Ok(Self::Pkcs1),
+            "pkcs8" => Ok(Self::Pkcs8),
+            "jwk" => Ok(Self::Jwk),
+            "openssh" => Ok(Self::OpenSsh),
+            _ => Err(Error::Unsupported("Unknown key format")),
+        }
+    }
+}
+
+fn is_jwk(input: &str) -> bool {
+    input.trim_start().starts_with('{')
+}
+
+/// `AlgorithmIdentifier { rsaEncryption, NULL }`
+fn rsa_algorithm_identifier() -> Vec<u8> {
+    der::sequence(&[der::oid(RSA_ENCRYPTION_OID), der::null()])
+}
+
+fn read_rsa_algorithm_identifier(reader: &mut Reader) -> Result<(), Error> {
+    let mut alg = reader.read_sequence()?;
+    if alg.read_oid()? != RSA_ENCRYPTION_OID {
+        return Err(Error::Unsupported("Not an RSA key"));
+    }
+    // 参数应为 NULL，也兼容省略的写法
+    if !alg.is_empty() {
+        alg.read_null()?;
+    }
+    alg.finish()
+}
+
+impl RsaPublicKey {
+    /// PKCS#1 `RSAPublicKey ::= SEQUENCE { n, e }`
+    pub fn to_pkcs1_der(&self) -> Vec<u8> {
+        der::sequence(&[der::integer(&self.n), der::integer(&self.e)])
+    }
+
+    pub fn from_pkcs1_der(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(data);
+        let mut seq = reader.read_sequence()?;
+        reader.finish()?;
+        let n = seq.read_integer()?;
+        let e = seq.read_integer()?;
+        seq.finish()?;
+        Self::new(n, e)
+    }
+
+    /// X.509 `SubjectPublicKeyInfo`
+    pub fn to_spki_der(&self) -> Vec<u8> {
+        der::sequence(&[
+            rsa_algorithm_identifier(),
+            der::bit_string(&self.to_pkcs1_der()),
+        ])
+    }
+
+    pub fn from_spki_der(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(data);
+        let mut seq = reader.read_sequence()?;
+        reader.finish()?;
+        read_rsa_algorithm_identifier(&mut seq)?;
+        let key = seq.read_bit_string()?;
+        seq.finish()?;
+        Self::from_pkcs1_der(key)
+    }
+
+    pub fn encode(&self, format: KeyFormat) -> String {
+        match format {
+            KeyFormat::Hex => self.fmt_hex(),
+            KeyFormat::Pkcs1 => pem::encode(PKCS1_PUBLIC_LABEL, &self.to_pkcs1_der()),
+            KeyFormat::Pkcs8 => pem::encode(SPKI_LABEL, &self.to_spki_der()),
+            KeyFormat::Jwk => self.to_jwk().to_string(),
+            KeyFormat::OpenSsh => self.to_openssh(),
+        }
+    }
+
+    /// 自动识别十六进制、PEM、JWK 或 OpenSSH 格式的公钥
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        if is_jwk(input) {
+            return Self::from_jwk(&Jwk::from_json(input)?);
+        }
+        if openssh::is_openssh(input) {
+            return Self::from_openssh(input);
+        }
+        if !pem::is_pem(input) {
+            return Self::from_hex(input.trim());
+        }
+        match pem::decode(input)? {
+            (PKCS1_PUBLIC_LABEL, der) => Self::from_pkcs1_der(&der),
+            (SPKI_LABEL, der) => Self::from_spki_der(&der),
+            _ => Err(Error::Unsupported("Unsupported PEM label for public key")),
+        }
+    }
+}
+
+impl RsaPrivateKey {
+    /// PKCS#1 `RSAPrivateKey`，需要 CRT 参数
+    pub fn to_pkcs1_der(&self) -> Result<Vec<u8>, Error> {
+        let crt = self
+            .crt
+            .as_ref()
+            .ok_or(Error::InvalidKey("Private key has no CRT parameters"))?;
+        Ok(der::sequence(&[
+            der::integer(&ZERO),
+            der::integer(&self.n),
+            der::integer(&self.e),
+            der::integer(&self.d),
+            der::integer(&crt.p),
+            der::integer(&crt.q),
+            der::integer(&crt.dp),
+            der::integer(&crt.dq),
+            der::integer(&crt.qinv),
+        ]))
+    }
+
+    /// 只支持两个素数的密钥，CRT 参数根据 p、q 重新计算
+    pub fn from_pkcs1_der(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(data);
+        let mut seq = reader.read_sequence()?;
+        reader.finish()?;
+        if !seq.read_integer()?.is_zero() {
+            return Err(Error::Unsupported("Unsupported RSAPrivateKey version"));
+        }
+        let n = seq.read_integer()?;
+        let e = seq.read_integer()?;
+        let d = seq.read_integer()?;
+        let p = seq.read_integer()?;
+        let q = seq.read_integer()?;
+        for _ in 0..3 {
+            seq.read_integer()?;
+        }
+        seq.finish()?;
+        Self::new(n, e, d)?.with_primes(&p, &q)
+    }
+
+    /// PKCS#8 `PrivateKeyInfo`
+    pub fn to_pkcs8_der(&self) -> Result<Vec<u8>, Error> {
+        Ok(der::sequence(&[
+            der::integer(&ZERO),
+            rsa_algorithm_identifier(),
+            der::octet_string(&self.to_pkcs1_der()?),
+        ]))
+    }
+
+    pub fn from_pkcs8_der(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(data);
+        let mut seq = reader.read_sequence()?;
+        reader.finish()?;
+        if !seq.read_integer()?.is_zero() {
+            return Err(Error::Unsupported("Unsupported PrivateKeyInfo version"));
+        }
+        read_rsa_algorithm_identifier(&mut seq)?;
+        // 其后可能带有可选的 attributes，忽略即可
+        Self::from_pkcs1_der(seq.read_octet_string()?)
+    }
+
+    /// PBES2 加密的 PKCS#8 `EncryptedPrivateKeyInfo`
+    pub fn to_encrypted_pkcs8_der(&self, passphrase: &str) -> Result<Vec<u8>, Error> {
+        Ok(pbes2::encrypt(&self.to_pkcs8_der()?, passphrase))
+    }
+
+    pub fn from_encrypted_pkcs8_der(data: &[u8], passphrase: &str) -> Result<Self, Error> {
+        Self::from_pkcs8_der(&pbes2::decrypt(data, passphrase)?)
+    }
+
+    /// 给出 `passphrase` 时输出加密的 PKCS#8，只支持 `KeyFormat::Pkcs8` 和 `KeyFormat::OpenSsh`
+    pub fn encode(&self, format: KeyFormat, passphrase: Option<&str>) -> Result<String, Error> {
+        match (format, passphrase) {
+            (KeyFormat::Hex, None) => Ok(self.fmt_hex()),
+            (KeyFormat::Pkcs1, None) => Ok(pem::encode(PKCS1_PRIVATE_LABEL, &self.to_pkcs1_der()?)),
+            (KeyFormat::Pkcs8, None) => Ok(pem::encode(PKCS8_LABEL, &self.to_pkcs8_der()?)),
+            (KeyFormat::OpenSsh, None) => self.encode(KeyFormat::Pkcs1, None),
+            (KeyFormat::Pkcs8 | KeyFormat::OpenSsh, Some(passphrase)) => Ok(pem::encode(
+                ENCRYPTED_PKCS8_LABEL,
+                &self.to_encrypted_pkcs8_der(passphrase)?,
+            )),
+            (KeyFormat::Jwk, None) => Ok(self.to_jwk().to_string()),
+            (_, Some(_)) => Err(Error::Unsupported(
+                "Only PKCS#8 private keys can be encrypted",
+            )),
+        }
+    }
+
+    /// 自动识别十六进制、PEM 或 JWK 格式的私钥，其中的 n、e 必须与 `public_key` 一致，
+    /// 加密的 PKCS#8 需要提供 `passphrase`
+    pub fn parse(
+        public_key: RsaPublicKey,
+        input: &str,
+        passphrase: Option<&str>,
+    ) -> Result<Self, Error> {
+        if !is_jwk(input) && !pem::is_pem(input) {
+            return Self::from_hex(public_key, input.trim());
+        }
+        let key = if is_jwk(input) {
+            Self::from_jwk(&Jwk::from_json(input)?)?
+        } else {
+            Self::from_pem(input, passphrase)?
+        };
+        if key.public_key() != public_key {
+            return Err(Error::InvalidKey("Private key does not match public key"));
+        }
+        Ok(key)
+    }
+
+    fn from_pem(input: &str, passphrase: Option<&str>) -> Result<Self, Error> {
+        match pem::decode(input)? {
+            (PKCS1_PRIVATE_LABEL, der) => Self::from_pkcs1_der(&der),
+            (PKCS8_LABEL, der) => Self::from_pkcs8_der(&der),
+            (ENCRYPTED_PKCS8_LABEL, der) => {
+                let passphrase = passphrase.ok_or(Error::InvalidInput(
+                    "Passphrase required for encrypted private key",
+                ))?;
+                Self::from_encrypted_pkcs8_der(&der, passphrase)
+            }
+            _ => Err(Error::Unsupported("Unsupported PEM label for private key")),
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rand::Rng;
+
+use crate::error::Error;
+use crate::pbes2;
+use crate::rsa::{RsaPrivateKey, RsaPublicKey};
+
+/// 密钥库文件的格式版本
+const VERSION: u32 = 1;
+const DEFAULT_PATH: &str = "keystore.json";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const KEY_ID_LEN: usize = 16;
+
+const NOT_FOUND: Error = Error::NotFound("Key not found");
+
+/// 从口令派生主密钥时使用的参数
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct KdfParams {
+    /// base64
+    salt: String,
+    iterations: u32,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+struct StoredKey {
+    key_id: String,
+    created_at: u64,
+    /// `n,e`
+    public_key: String,
+    /// base64
+    nonce: String,
+    /// 用主密钥以 AES-256-GCM 加密的私钥，附加数据见 `aad`
+    private_key: String,
+}
+
+impl StoredKey {
+    /// 把私钥与 ID、创建时间和公钥绑定，防止这些字段被单独替换
+    fn aad(&self) -> Vec<u8> {
+        format!("{}:{}:{}", self.key_id, self.created_at, self.public_key).into_bytes()
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct StoreFile {
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfParams>,
+    keys: Vec<StoredKey>,
+}
+
+struct Entry {
+    key: RsaPrivateKey,
+    stored: StoredKey,
+}
+
+/// 服务端保存的密钥，提供主密钥时持久化到磁盘，私钥部分加密存储
+pub struct Keystore {
+    path: Option<PathBuf>,
+    master_key: [u8; 32],
+    kdf: Option<KdfParams>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl Keystore {
+    /// 只在内存中保存，重启后丢失
+    pub fn in_memory() -> Self {
+        let mut master_key = [0u8; 32];
+        rand::thread_rng().fill(&mut master_key);
+        Self {
+            path: None,
+            master_key,
+            kdf: None,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 打开 `path` 处的密钥库，文件不存在时新建。
+    /// 给出 `passphrase` 时用 PBKDF2 派生主密钥，否则直接使用 `master_key`
+    pub fn open(
+        path: PathBuf,
+        master_key: Option<[u8; 32]>,
+        passphrase: Option<&str>,
+    ) -> Result<Self, Error> {
+        let file = match fs::read(&path) {
+            Ok(data) => Some(
+                serde_json::from_slice::<StoreFile>(&data)
+                    .map_err(|_| Error::Parse("Malformed keystore file"))?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(_) => return Err(Error::Internal("Failed to read keystore file")),
+        };
+        if file.as_ref().is_some_and(|f| f.version != VERSION) {
+            return Err(Error::Unsupported("Unsupported keystore version"));
+        }
+
+        let (master_key, kdf) = match (master_key, passphrase) {
+            (Some(key), _) => (key, None),
+            (None, Some(passphrase)) => {
+                let kdf = match file.as_ref().and_then(|f| f.kdf.clone()) {
+                    Some(kdf) => kdf,
+                    None => {
+                        let mut salt = [0u8; SALT_LEN];
+                        rand::thread_rng().fill(&mut salt);
+                        KdfParams {
+                            salt: BASE64.encode(salt),
+                            iterations: pbes2::ITERATIONS,
+                        }
+                    }
+                };
+                let salt = BASE64
+                    .decode(&kdf.salt)
+                    .map_err(|_| Error::Parse("Malformed keystore file"))?;
+                (
+                    pbes2::derive_key(passphrase, &salt, kdf.iterations),
+                    Some(kdf),
+                )
+            }
+            (None, None) => {
+                return Err(Error::InvalidInput(
+                    "Keystore requires a master key or passphrase",
+                ))
+            }
+        };
+
+        let store = Self {
+            path: Some(path),
+            master_key,
+            kdf,
+            entries: Mutex::new(HashMap::new()),
+        };
+        if let Some(file) = file {
+            let mut entries = store.entries.lock().unwrap();
+            for stored in file.keys {
+                let key = store.unseal(&stored)?;
+                entries.insert(stored.key_id.clone(), Entry { key, stored });
+            }
+        }
+        Ok(store)
+    }
+
+    /// 读取 `RSA_KEYSTORE_PATH`、`RSA_KEYSTORE_KEY`（64 位十六进制）和 `RSA_KEYSTORE_PASSPHRASE`，
+    /// 两者都未设置时退化为内存中的密钥库
+    pub fn from_env() -> Result<Self, Error> {
+        let master_key = match env::var("RSA_KEYSTORE_KEY") {
+            Ok(hex) => Some(parse_master_key(&hex)?),
+            Err(_) => None,
+        };
+        let passphrase = env::var("RSA_KEYSTORE_PASSPHRASE").ok();
+        if master_key.is_none() && passphrase.is_none() {
+            return Ok(Self::in_memory());
+        }
+        let path = env::var("RSA_KEYSTORE_PATH").unwrap_or(DEFAULT_PATH.to_owned());
+        Self::open(PathBuf::from(path), master_key, passphrase.as_deref())
+    }
+
+    /// 是否保存到磁盘，否则重启后密钥丢失
+    pub fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(&self.master_key.into())
+    }
+
+    fn seal(&self, key_id: &str, created_at: u64, key: &RsaPrivateKey) -> Result<StoredKey, Error> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill(&mut nonce);
+        let mut stored = StoredKey {
+            key_id: key_id.to_owned(),
+            created_at,
+            public_key: key.public_key().fmt_hex(),
+            nonce: BASE64.encode(nonce),
+            private_key: String::new(),
+        };
+        let ciphertext = self
+            .cipher()
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: key.fmt_hex().as_bytes(),
+                    aad: &stored.aad(),
+                },
+            )
+            .map_err(|_| Error::Internal("Encryption failed"))?;
+        stored.private_key = BASE64.encode(ciphertext);
+        Ok(stored)
+    }
+
+    fn unseal(&self, stored: &StoredKey) -> Result<RsaPrivateKey, Error> {
+        let nonce = BASE64
+            .decode(&stored.nonce)
+            .map_err(|_| Error::Parse("Malformed keystore file"))?;
+        let ciphertext = BASE64
+            .decode(&stored.private_key)
+            .map_err(|_| Error::Parse("Malformed keystore file"))?;
+        if nonce.len() != NONCE_LEN {
+            return Err(Error::Parse("Malformed keystore file"));
+        }
+        let plain = self
+            .cipher()
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &stored.aad(),
+                },
+            )
+            .map_err(|_| Error::Decryption("Wrong keystore master key or corrupted keystore"))?;
+        let hex = String::from_utf8(plain).map_err(|_| Error::Parse("Malformed keystore file"))?;
+        RsaPrivateKey::from_hex(RsaPublicKey::from_hex(&stored.public_key)?, &hex)
+    }
+
+    /// 先写入临时文件再重命名，保证文件总是完整的
+    fn persist(&self, entries: &HashMap<String, Entry>) -> Result<(), Error> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut keys = entries
+            .values()
+            .map(|e| e.stored.clone())
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|k| (k.created_at, k.key_id.clone()));
+        let file = StoreFile {
+            version: VERSION,
+            kdf: self.kdf.clone(),
+            keys,
+        };
+        let data = serde_json::to_vec_pretty(&file)
+            .map_err(|_| Error::Internal("Failed to serialize keystore"))?;
+
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let write = || -> std::io::Result<()> {
+            let mut f = fs::File::create(&tmp)?;
+            f.write_all(&data)?;
+            f.sync_all()?;
+            fs::rename(&tmp, path)
+        };
+        write().map_err(|_| Error::Internal("Failed to write keystore file"))
+    }
+
+    /// 保存私钥并返回新分配的 `key_id`
+    pub fn insert(&self, key: RsaPrivateKey) -> Result<String, Error> {
+        let mut id = [0u8; KEY_ID_LEN];
+        rand::thread_rng().fill(&mut id);
+        let key_id = id.iter().map(|b| format!("{b:02x}")).collect::<String>();
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|_| Error::Internal("System clock is before 1970"))?
+            .as_secs();
+
+        let stored = self.seal(&key_id, created_at, &key)?;
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key_id.clone(), Entry { key, stored });
+        if let Err(e) = self.persist(&entries) {
+            entries.remove(&key_id);
+            return Err(e);
+        }
+        Ok(key_id)
+    }
+
+    pub fn private_key(&self, key_id: &str) -> Result<RsaPrivateKey, Error> {
+        let entries = self.entries.lock().unwrap();
+        entries.get(key_id).map(|e| e.key.clone()).ok_or(NOT_FOUND)
+    }
+
+    pub fn public_key(&self, key_id: &str) -> Result<RsaPublicKey, Error> {
+        Ok(self.private_key(key_id)?.public_key())
+    }
+
+    /// 按创建时间排序的 `(key_id, created_at, 公钥)`
+    pub fn list(&self) -> Vec<(String, u64, RsaPublicKey)> {
+        let entries = self.entries.lock().unwrap();
+        let mut res = entries
+            .values()
+            .map(|e| {
+                (
+                    e.stored.key_id.clone(),
+                    e.stored.created_at,
+                    e.key.public_key(),
+                )
+            })
+            .collect::<Vec<_>>();
+        res.sort_by(|a, b| (a.1, &a.0).cmp(&(b.1, &b.0)));
+        res
+    }
+
+    /// 删除并返回对应的公钥
+    pub fn remove(&self, key_id: &str) -> Result<RsaPublicKey, Error> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.remove(key_id).ok_or(NOT_FOUND)?;
+        if let Err(e) = self.persist(&entries) {
+            entries.insert(key_id.to_owned(), entry);
+            return Err(e);
+        }
+        Ok(entry.key.public_key())
+    }
+}
+
+fn parse_master_key(hex: &str) -> Result<[u8; 32], Error> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(Error::InvalidInput(
+            "RSA_KEYSTORE_KEY must be 64 hex digits",
+        ));
+    }
+    let mut key = [0u8; 32];
+    for (i, b) in key.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+            .map_err(|_| Error::InvalidInput("RSA_KEYSTORE_KEY must be 64 hex digits"))?;
+    }
+    Ok(key)
+}
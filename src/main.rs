@@ -1,33 +1,92 @@
 use std::env;
+use std::process;
+use std::sync::Arc;
 
+use backend::jobs::KeyGenJobs;
 use backend::routes::*;
-use rocket::{launch, routes};
-use rocket_cors::{AllowedOrigins, Cors, CorsOptions};
+use keystore::Keystore;
+use rocket::data::{Limits, ToByteUnit};
+use rocket::fairing::AdHoc;
+use rocket::{catchers, routes, Build, Rocket};
+use rocket_cors::{AllowedOrigins, CorsOptions};
 
 mod algorithms;
 mod backend;
 mod bigint;
+mod cli;
+mod der;
+mod envelope;
+mod error;
+mod hash;
+mod jwk;
+mod key_format;
+mod keystore;
+mod merkle;
+mod openssh;
+mod padding;
+mod pbes2;
+mod pem;
 mod rsa;
 
-#[launch]
-fn rocket() -> _ {
+/// 带参数时作为命令行工具运行，否则启动 HTTP 服务
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if !args.is_empty() {
+        process::exit(cli::run(&args));
+    }
+    // 与 `#[launch]` 相同，启动失败时由 `rocket::Error` 打印原因
+    let _ = rocket::execute(rocket().launch());
+}
+
+fn rocket() -> Rocket<Build> {
     let port: u16 = match env::var("RUST_API_PORT") {
-        Ok(value) => {
-            if let Ok(value) = u16::from_str_radix(&value, 10) {
-                value
-            } else {
-                8080
-            }
-        },
+        Ok(value) => value.parse().unwrap_or(8080),
         Err(_) => 8080,
     };
     let cors = CorsOptions::default().allowed_origins(AllowedOrigins::all());
+    let keystore = Arc::new(Keystore::from_env().expect("Failed to open keystore"));
     rocket::build()
         .attach(cors.to_cors().unwrap())
-        .mount("/", routes![key_gen, encrypt, decrypt, sign, verify_sign])
+        .mount(
+            "/",
+            routes![
+                key_gen,
+                create_keygen_job,
+                get_keygen_job,
+                keygen_job_events,
+                cancel_keygen_job,
+                encrypt,
+                decrypt,
+                sign,
+                verify_sign,
+                seal,
+                open,
+                list_keys,
+                get_public_key,
+                delete_key,
+                merkle_sign,
+                merkle_verify_chunk
+            ],
+        )
+        .register("/", catchers![default_catcher])
+        .attach(AdHoc::on_liftoff("Keystore", |rocket| {
+            Box::pin(async move {
+                if rocket
+                    .state::<Arc<Keystore>>()
+                    .is_some_and(|keystore| !keystore.is_persistent())
+                {
+                    rocket::warn!(
+                        "RSA_KEYSTORE_KEY or RSA_KEYSTORE_PASSPHRASE is not set, keys will not be persisted"
+                    );
+                }
+            })
+        }))
+        .manage(KeyGenJobs::from_env(keystore.clone()))
+        .manage(keystore)
         .configure(rocket::Config {
             port,
             address: "0.0.0.0".parse().unwrap(),
+            limits: Limits::default().limit("json", MAX_REQUEST_LEN.bytes()),
             ..rocket::Config::default()
         })
 }
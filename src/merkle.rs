@@ -0,0 +1,252 @@
+use std::io::Read;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use crate::error::Error;
+use crate::hash::HashAlgorithm;
+use crate::rsa::{self, RsaPrivateKey, RsaPublicKey, SignatureScheme};
+
+/// 当前的签名格式版本
+const VERSION: u8 = 1;
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// 块过小时树和全部证明的大小会远超原始数据
+const MIN_CHUNK_SIZE: usize = 1024;
+const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// 叶子与内部节点使用不同的前缀（同 RFC 6962），防止把内部节点当作数据块
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+fn check_chunk_size(chunk_size: usize) -> Result<(), Error> {
+    if !(MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&chunk_size) {
+        return Err(Error::InvalidInput(
+            "Chunk size must be between 1 KiB and 16 MiB",
+        ));
+    }
+    Ok(())
+}
+
+fn leaf_hash(hash: HashAlgorithm, chunk: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(chunk.len() + 1);
+    data.push(LEAF_PREFIX);
+    data.extend_from_slice(chunk);
+    hash.digest(&data)
+}
+
+fn node_hash(hash: HashAlgorithm, left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(left.len() + right.len() + 1);
+    data.push(NODE_PREFIX);
+    data.extend_from_slice(left);
+    data.extend_from_slice(right);
+    hash.digest(&data)
+}
+
+/// 按固定大小分块构建的 Merkle 树，某一层节点数为奇数时最后一个节点直接升到上一层
+pub struct MerkleTree {
+    hash: HashAlgorithm,
+    chunk_size: usize,
+    length: u64,
+    /// `levels[0]` 为叶子，最后一层只有根
+    levels: Vec<Vec<Vec<u8>>>,
+}
+
+impl MerkleTree {
+    /// 空输入视为一个空块
+    pub fn from_reader<R: Read>(
+        mut reader: R,
+        chunk_size: usize,
+        hash: HashAlgorithm,
+    ) -> Result<Self, Error> {
+        check_chunk_size(chunk_size)?;
+        let mut leaves = vec![];
+        let mut length = 0u64;
+        let mut chunk = Vec::with_capacity(chunk_size);
+        loop {
+            chunk.clear();
+            (&mut reader)
+                .take(chunk_size as u64)
+                .read_to_end(&mut chunk)
+                .map_err(|_| Error::Internal("Failed to read data"))?;
+            if chunk.is_empty() && !leaves.is_empty() {
+                break;
+            }
+            length += chunk.len() as u64;
+            leaves.push(leaf_hash(hash, &chunk));
+            if chunk.len() < chunk_size {
+                break;
+            }
+        }
+
+        let mut levels = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(hash, left, right),
+                    [node] => node.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Ok(Self {
+            hash,
+            chunk_size,
+            length,
+            levels,
+        })
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn root(&self) -> &[u8] {
+        &self.levels.last().unwrap()[0]
+    }
+
+    /// 第 `index` 块的包含证明，即自底向上的兄弟节点（base64），被直接升层的节点没有兄弟
+    pub fn proof(&self, index: usize) -> Result<Vec<String>, Error> {
+        if index >= self.chunk_count() {
+            return Err(Error::InvalidInput("Chunk index out of range"));
+        }
+        let mut proof = vec![];
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(BASE64.encode(sibling));
+            }
+            index /= 2;
+        }
+        Ok(proof)
+    }
+
+    /// 用 RSASSA-PSS 对根及分块参数签名
+    pub fn sign(&self, key: &RsaPrivateKey) -> Result<MerkleSignature, Error> {
+        let mut signature = MerkleSignature {
+            version: VERSION,
+            hash: self.hash,
+            chunk_size: self.chunk_size,
+            length: self.length,
+            root: BASE64.encode(self.root()),
+            signature: String::new(),
+        };
+        signature.signature = rsa::sign(
+            &signature.manifest(),
+            key,
+            SignatureScheme::Pss,
+            self.hash,
+            None,
+        )?;
+        Ok(signature)
+    }
+}
+
+/// 对 Merkle 根的签名，自带验证单个数据块所需的全部参数
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MerkleSignature {
+    pub version: u8,
+    pub hash: HashAlgorithm,
+    pub chunk_size: usize,
+    /// 原始数据的总字节数
+    pub length: u64,
+    /// base64
+    pub root: String,
+    /// RSASSA-PSS 签名，格式与 `rsa::sign` 的输出相同
+    pub signature: String,
+}
+
+impl MerkleSignature {
+    /// 实际被签名的内容，包含全部参数，防止块大小或长度被替换
+    fn manifest(&self) -> String {
+        format!(
+            "rsa-merkle:{}:{}:{}:{}:{}",
+            self.version,
+            self.hash.name(),
+            self.chunk_size,
+            self.length,
+            self.root
+        )
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        if self.version != VERSION {
+            return Err(Error::Unsupported("Unsupported Merkle signature version"));
+        }
+        check_chunk_size(self.chunk_size)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.length.div_ceil(self.chunk_size as u64).max(1) as usize
+    }
+
+    /// 只验证根上的签名
+    pub fn verify(&self, key: &RsaPublicKey) -> Result<bool, Error> {
+        self.check()?;
+        rsa::ver_sign(
+            &self.manifest(),
+            &self.signature,
+            key,
+            SignatureScheme::Pss,
+            self.hash,
+            None,
+        )
+    }
+
+    /// 重新计算完整数据的 Merkle 根并验证
+    pub fn verify_data<R: Read>(&self, key: &RsaPublicKey, reader: R) -> Result<bool, Error> {
+        self.check()?;
+        let tree = MerkleTree::from_reader(reader, self.chunk_size, self.hash)?;
+        if tree.length != self.length || BASE64.encode(tree.root()) != self.root {
+            return Ok(false);
+        }
+        self.verify(key)
+    }
+
+    /// 用包含证明验证第 `index` 块，块的位置由 `index` 决定，不能被挪用到其他位置
+    pub fn verify_chunk(
+        &self,
+        key: &RsaPublicKey,
+        index: usize,
+        chunk: &[u8],
+        proof: &[String],
+    ) -> Result<bool, Error> {
+        self.check()?;
+        let count = self.chunk_count();
+        if index >= count {
+            return Err(Error::InvalidInput("Chunk index out of range"));
+        }
+        let offset = index as u64 * self.chunk_size as u64;
+        if chunk.len() as u64 != (self.length - offset).min(self.chunk_size as u64) {
+            return Ok(false);
+        }
+
+        let mut node = leaf_hash(self.hash, chunk);
+        let mut proof = proof.iter();
+        let (mut index, mut width) = (index, count);
+        while width > 1 {
+            if index % 2 == 1 || index + 1 < width {
+                let sibling = proof.next().ok_or(Error::Parse("Merkle proof too short"))?;
+                let sibling = BASE64
+                    .decode(sibling)
+                    .map_err(|_| Error::Parse("Invalid base64"))?;
+                node = if index % 2 == 1 {
+                    node_hash(self.hash, &sibling, &node)
+                } else {
+                    node_hash(self.hash, &node, &sibling)
+                };
+            }
+            index /= 2;
+            width = width.div_ceil(2);
+        }
+        if proof.next().is_some() {
+            return Err(Error::Parse("Merkle proof too long"));
+        }
+        if BASE64.encode(node) != self.root {
+            return Ok(false);
+        }
+        self.verify(key)
+    }
+}
@@ -0,0 +1,114 @@
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, STANDARD_NO_PAD as BASE64_NO_PAD},
+    Engine,
+};
+use md5::{Digest, Md5};
+
+use crate::bigint::BigInt;
+use crate::error::Error;
+use crate::hash::HashAlgorithm;
+use crate::rsa::RsaPublicKey;
+
+const KEY_TYPE: &str = "ssh-rsa";
+
+/// RFC 4251 `string`：4 字节大端长度加内容
+fn write_string(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend((data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+/// RFC 4251 `mpint`，非负数最高位为 1 时在前面补 0
+fn write_mpint(buf: &mut Vec<u8>, x: &BigInt) {
+    let mut bytes = x.to_bytes_be(0);
+    if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    write_string(buf, &bytes);
+}
+
+fn read_string<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    if data.len() < 4 {
+        return Err(Error::Parse("Invalid OpenSSH public key"));
+    }
+    let (len, rest) = data.split_at(4);
+    let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+    if rest.len() < len {
+        return Err(Error::Parse("Invalid OpenSSH public key"));
+    }
+    let (res, rest) = rest.split_at(len);
+    *data = rest;
+    Ok(res)
+}
+
+fn read_mpint(data: &mut &[u8]) -> Result<BigInt, Error> {
+    let bytes = read_string(data)?;
+    if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+        return Err(Error::Parse("Negative mpint in OpenSSH public key"));
+    }
+    Ok(BigInt::from_bytes_be(bytes))
+}
+
+impl RsaPublicKey {
+    /// RFC 4253 6.6 的 `ssh-rsa` 公钥编码：`string "ssh-rsa" || mpint e || mpint n`
+    pub fn to_openssh_blob(&self) -> Vec<u8> {
+        let mut blob = vec![];
+        write_string(&mut blob, KEY_TYPE.as_bytes());
+        write_mpint(&mut blob, &self.e);
+        write_mpint(&mut blob, &self.n);
+        blob
+    }
+
+    pub fn from_openssh_blob(blob: &[u8]) -> Result<Self, Error> {
+        let mut data = blob;
+        if read_string(&mut data)? != KEY_TYPE.as_bytes() {
+            return Err(Error::Unsupported("Not an ssh-rsa public key"));
+        }
+        let e = read_mpint(&mut data)?;
+        let n = read_mpint(&mut data)?;
+        if !data.is_empty() {
+            return Err(Error::Parse("Invalid OpenSSH public key"));
+        }
+        Self::new(n, e)
+    }
+
+    /// `authorized_keys` 中的一行，不带注释
+    pub fn to_openssh(&self) -> String {
+        format!("{KEY_TYPE} {}", BASE64.encode(self.to_openssh_blob()))
+    }
+
+    /// 解析 `ssh-rsa AAAA... [comment]`
+    pub fn from_openssh(line: &str) -> Result<Self, Error> {
+        let mut parts = line.split_ascii_whitespace();
+        if parts.next() != Some(KEY_TYPE) {
+            return Err(Error::Unsupported("Not an ssh-rsa public key"));
+        }
+        let blob = parts
+            .next()
+            .ok_or(Error::Parse("Invalid OpenSSH public key"))?;
+        let blob = BASE64
+            .decode(blob)
+            .map_err(|_| Error::Parse("Invalid OpenSSH public key"))?;
+        Self::from_openssh_blob(&blob)
+    }
+
+    /// 与 `ssh-keygen -l` 相同的 `SHA256:...` 指纹
+    pub fn fingerprint_sha256(&self) -> String {
+        let digest = HashAlgorithm::Sha256.digest(&self.to_openssh_blob());
+        format!("SHA256:{}", BASE64_NO_PAD.encode(digest))
+    }
+
+    /// 与 `ssh-keygen -l -E md5` 相同的 `MD5:xx:xx:...` 指纹
+    pub fn fingerprint_md5(&self) -> String {
+        let digest = Md5::digest(self.to_openssh_blob());
+        let hex = digest
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(":");
+        format!("MD5:{hex}")
+    }
+}
+
+pub fn is_openssh(input: &str) -> bool {
+    input.trim_start().starts_with(KEY_TYPE)
+}
@@ -0,0 +1,459 @@
+use rand::Rng;
+
+use crate::error::Error;
+use crate::hash::HashAlgorithm;
+
+/// 填充方案使用的哈希函数
+pub trait PaddingHash: Copy {
+    fn output_len(&self) -> usize;
+    fn digest(&self, data: &[u8]) -> Vec<u8>;
+}
+
+impl PaddingHash for HashAlgorithm {
+    fn output_len(&self) -> usize {
+        HashAlgorithm::output_len(self)
+    }
+
+    fn digest(&self, data: &[u8]) -> Vec<u8> {
+        HashAlgorithm::digest(self, data)
+    }
+}
+
+/// 解密时所有填充错误统一返回的错误，避免泄露具体是哪一步失败
+pub const DECRYPTION_ERROR: Error = Error::Decryption("Decryption error");
+
+/// EME-PKCS1-v1_5 编码：`0x00 || 0x02 || PS || 0x00 || M`，PS 为至少 8 字节的非零随机数
+pub fn pkcs1_v15_pad(message: &[u8], k: usize) -> Result<Vec<u8>, Error> {
+    if message.len() + 11 > k {
+        return Err(Error::MessageTooLong);
+    }
+    let mut rng = rand::thread_rng();
+    let mut em = Vec::with_capacity(k);
+    em.extend([0x00, 0x02]);
+    em.extend((0..k - message.len() - 3).map(|_| rng.gen_range(1..=0xff_u8)));
+    em.push(0x00);
+    em.extend_from_slice(message);
+    Ok(em)
+}
+
+/// EME-PKCS1-v1_5 解码，扫描过程不提前返回
+pub fn pkcs1_v15_unpad(em: &[u8]) -> Result<Vec<u8>, Error> {
+    if em.len() < 11 {
+        return Err(DECRYPTION_ERROR);
+    }
+
+    let mut invalid = em[0] | (em[1] ^ 0x02);
+    let mut found: u8 = 0;
+    let mut separator: usize = 0;
+    for (i, b) in em.iter().enumerate().skip(2) {
+        let is_zero = (*b == 0) as u8;
+        // 只记录第一个 0x00 的位置
+        let first = is_zero & !found & 1;
+        separator |= i * first as usize;
+        found |= is_zero;
+    }
+    invalid |= !found & 1;
+    invalid |= (separator < 10) as u8;
+
+    if invalid != 0 {
+        return Err(DECRYPTION_ERROR);
+    }
+    Ok(em[separator + 1..].to_vec())
+}
+
+/// MGF1 掩码生成函数，输出 `len` 字节
+pub fn mgf1<H: PaddingHash>(hash: H, seed: &[u8], len: usize) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(len + hash.output_len());
+    let mut counter: u32 = 0;
+    while mask.len() < len {
+        let mut input = seed.to_vec();
+        input.extend(counter.to_be_bytes());
+        mask.extend(hash.digest(&input));
+        counter += 1;
+    }
+    mask.truncate(len);
+    mask
+}
+
+fn xor_in_place(data: &mut [u8], mask: &[u8]) {
+    for (d, m) in data.iter_mut().zip(mask) {
+        *d ^= m;
+    }
+}
+
+/// EME-OAEP 编码：`0x00 || maskedSeed || maskedDB`，`DB = lHash || PS || 0x01 || M`
+pub fn oaep_pad<H: PaddingHash>(
+    message: &[u8],
+    k: usize,
+    hash: H,
+    label: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut seed = vec![0; hash.output_len()];
+    rand::thread_rng().fill(&mut seed[..]);
+    oaep_pad_with_seed(message, k, hash, label, seed)
+}
+
+/// 同 `oaep_pad`，使用给定的 `seed`
+fn oaep_pad_with_seed<H: PaddingHash>(
+    message: &[u8],
+    k: usize,
+    hash: H,
+    label: &[u8],
+    mut seed: Vec<u8>,
+) -> Result<Vec<u8>, Error> {
+    let h_len = hash.output_len();
+    if message.len() + 2 * h_len + 2 > k {
+        return Err(Error::MessageTooLong);
+    }
+
+    let mut db = hash.digest(label);
+    db.resize(k - message.len() - h_len - 2, 0);
+    db.push(0x01);
+    db.extend_from_slice(message);
+
+    xor_in_place(&mut db, &mgf1(hash, &seed, k - h_len - 1));
+    xor_in_place(&mut seed, &mgf1(hash, &db, h_len));
+
+    let mut em = Vec::with_capacity(k);
+    em.push(0x00);
+    em.extend(seed);
+    em.extend(db);
+    Ok(em)
+}
+
+/// EME-OAEP 解码，扫描过程不提前返回
+pub fn oaep_unpad<H: PaddingHash>(em: &[u8], hash: H, label: &[u8]) -> Result<Vec<u8>, Error> {
+    let h_len = hash.output_len();
+    if em.len() < 2 * h_len + 2 {
+        return Err(DECRYPTION_ERROR);
+    }
+
+    let (masked_seed, masked_db) = em[1..].split_at(h_len);
+    let mut seed = masked_seed.to_vec();
+    xor_in_place(&mut seed, &mgf1(hash, masked_db, h_len));
+    let mut db = masked_db.to_vec();
+    xor_in_place(&mut db, &mgf1(hash, &seed, masked_db.len()));
+
+    let mut invalid = em[0];
+    for (a, b) in db.iter().zip(hash.digest(label)) {
+        invalid |= a ^ b;
+    }
+    // lHash 之后是若干个 0x00，然后是 0x01
+    let mut found: u8 = 0;
+    let mut separator: usize = 0;
+    for (i, b) in db.iter().enumerate().skip(h_len) {
+        let is_one = (*b == 0x01) as u8;
+        let is_zero = (*b == 0x00) as u8;
+        let first = is_one & !found & 1;
+        separator |= i * first as usize;
+        // 在找到 0x01 之前出现非零字节即为错误
+        invalid |= !found & !is_zero & !is_one & 1;
+        found |= is_one;
+    }
+    invalid |= !found & 1;
+
+    if invalid != 0 {
+        return Err(DECRYPTION_ERROR);
+    }
+    Ok(db[separator + 1..].to_vec())
+}
+
+/// EMSA-PKCS1-v1_5 编码：`0x00 || 0x01 || PS || 0x00 || DigestInfo`，PS 为至少 8 字节的 0xff
+pub fn emsa_pkcs1_v15_encode(
+    message: &[u8],
+    k: usize,
+    hash: HashAlgorithm,
+) -> Result<Vec<u8>, Error> {
+    let mut t = hash.digest_info_prefix().to_vec();
+    t.extend(hash.digest(message));
+    if t.len() + 11 > k {
+        return Err(Error::Padding("Intended encoded message length too short"));
+    }
+    let mut em = Vec::with_capacity(k);
+    em.extend([0x00, 0x01]);
+    em.resize(k - t.len() - 1, 0xff);
+    em.push(0x00);
+    em.extend(t);
+    Ok(em)
+}
+
+/// 计算 `H = Hash(0x00 * 8 || mHash || salt)`
+fn pss_hash<H: PaddingHash>(hash: H, m_hash: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut m = vec![0u8; 8];
+    m.extend_from_slice(m_hash);
+    m.extend_from_slice(salt);
+    hash.digest(&m)
+}
+
+/// `em_len >= h_len + salt_len + 2`，`salt_len` 来自请求，不能直接相加
+fn pss_fits(em_len: usize, h_len: usize, salt_len: usize) -> bool {
+    h_len
+        .checked_add(salt_len)
+        .and_then(|len| len.checked_add(2))
+        .is_some_and(|len| em_len >= len)
+}
+
+/// EMSA-PSS 编码：`maskedDB || H || 0xbc`，`DB = PS || 0x01 || salt`，
+/// 输出 `ceil(em_bits / 8)` 字节，最高的 `8 * emLen - em_bits` 位为 0
+pub fn emsa_pss_encode<H: PaddingHash>(
+    message: &[u8],
+    em_bits: usize,
+    hash: H,
+    salt_len: usize,
+) -> Result<Vec<u8>, Error> {
+    let h_len = hash.output_len();
+    let em_len = em_bits.div_ceil(8);
+    if !pss_fits(em_len, h_len, salt_len) {
+        return Err(Error::Padding("Encoding error"));
+    }
+
+    let mut salt = vec![0; salt_len];
+    rand::thread_rng().fill(&mut salt[..]);
+    Ok(emsa_pss_encode_with_salt(message, em_bits, hash, salt))
+}
+
+/// 同 `emsa_pss_encode`，使用给定的 `salt`，调用方需保证长度合法
+fn emsa_pss_encode_with_salt<H: PaddingHash>(
+    message: &[u8],
+    em_bits: usize,
+    hash: H,
+    salt: Vec<u8>,
+) -> Vec<u8> {
+    let h_len = hash.output_len();
+    let em_len = em_bits.div_ceil(8);
+    let salt_len = salt.len();
+    let h = pss_hash(hash, &hash.digest(message), &salt);
+
+    let mut db = vec![0; em_len - salt_len - h_len - 2];
+    db.push(0x01);
+    db.extend(salt);
+    xor_in_place(&mut db, &mgf1(hash, &h, em_len - h_len - 1));
+    db[0] &= 0xff >> (8 * em_len - em_bits);
+
+    let mut em = db;
+    em.extend(h);
+    em.push(0xbc);
+    em
+}
+
+/// EMSA-PSS 验证，`em` 的长度必须恰好是 `ceil(em_bits / 8)`
+pub fn emsa_pss_verify<H: PaddingHash>(
+    message: &[u8],
+    em: &[u8],
+    em_bits: usize,
+    hash: H,
+    salt_len: usize,
+) -> bool {
+    let h_len = hash.output_len();
+    let em_len = em_bits.div_ceil(8);
+    if em.len() != em_len || !pss_fits(em_len, h_len, salt_len) || em[em_len - 1] != 0xbc {
+        return false;
+    }
+
+    let (masked_db, h) = em[..em_len - 1].split_at(em_len - h_len - 1);
+    let top_mask = 0xff >> (8 * em_len - em_bits);
+    if masked_db[0] & !top_mask != 0 {
+        return false;
+    }
+    let mut db = masked_db.to_vec();
+    xor_in_place(&mut db, &mgf1(hash, h, masked_db.len()));
+    db[0] &= top_mask;
+
+    let (ps, rest) = db.split_at(em_len - h_len - salt_len - 2);
+    if ps.iter().any(|b| *b != 0) || rest[0] != 0x01 {
+        return false;
+    }
+    pss_hash(hash, &hash.digest(message), &rest[1..]) == h
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use sha1::Digest;
+
+    use super::*;
+    use crate::algorithms::{ModPowBackend, ModPowContext};
+    use crate::bigint::BigInt;
+
+    /// RFC 8017 的测试向量使用 SHA-1，服务本身不支持，只在测试中使用
+    #[derive(Clone, Copy)]
+    struct Sha1;
+
+    impl PaddingHash for Sha1 {
+        fn output_len(&self) -> usize {
+            20
+        }
+
+        fn digest(&self, data: &[u8]) -> Vec<u8> {
+            sha1::Sha1::digest(data).to_vec()
+        }
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// 向量文件中的一段，名称到字节串
+    type Block<'a> = HashMap<&'a str, Vec<u8>>;
+
+    /// 向量文件按空行分段，第一段为公钥，之后每段一个例子，每行为 `名称: 十六进制`
+    fn parse_fixture(text: &str) -> (Block<'_>, Vec<Block<'_>>) {
+        let mut blocks = text
+            .split("\n\n")
+            .map(|block| {
+                block
+                    .lines()
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| {
+                        let (name, value) = line.split_once(": ").unwrap();
+                        (name, hex(value))
+                    })
+                    .collect::<Block>()
+            })
+            .filter(|block| !block.is_empty());
+        let key = blocks.next().unwrap();
+        (key, blocks.collect())
+    }
+
+    /// x^e mod n，结果左补零到 n 的字节数
+    fn rsa_public(key: &Block, x: &[u8]) -> Vec<u8> {
+        let n = BigInt::from_bytes_be(&key["n"]);
+        let ctx = ModPowContext::new(&n, ModPowBackend::Montgomery);
+        ctx.mod_power(&BigInt::from_bytes_be(x), &BigInt::from_bytes_be(&key["e"]))
+            .to_bytes_be(key["n"].len())
+    }
+
+    #[test]
+    fn oaep_rfc8017_vectors() {
+        let (key, examples) = parse_fixture(include_str!("../tests/fixtures/oaep-vect.txt"));
+        assert!(!examples.is_empty());
+        let k = key["n"].len();
+        for example in examples {
+            let message = &example["message"];
+            let em = oaep_pad_with_seed(message, k, Sha1, b"", example["seed"].clone()).unwrap();
+            assert_eq!(rsa_public(&key, &em), example["encryption"]);
+            assert_eq!(&oaep_unpad(&em, Sha1, b"").unwrap(), message);
+            assert_eq!(oaep_unpad(&em, Sha1, b"label"), Err(DECRYPTION_ERROR));
+        }
+    }
+
+    #[test]
+    fn oaep_rejects_tampered_message() {
+        let message = b"attack at dawn";
+        let mut em = oaep_pad(message, 256, HashAlgorithm::Sha256, b"label").unwrap();
+        assert_eq!(
+            oaep_unpad(&em, HashAlgorithm::Sha256, b"label").unwrap(),
+            message
+        );
+        em[0] = 1;
+        assert_eq!(
+            oaep_unpad(&em, HashAlgorithm::Sha256, b"label"),
+            Err(DECRYPTION_ERROR)
+        );
+        assert_eq!(
+            oaep_pad(&[0; 191], 256, HashAlgorithm::Sha256, b""),
+            Err(Error::MessageTooLong)
+        );
+    }
+
+    #[test]
+    fn pss_rfc8017_vectors() {
+        let (key, examples) = parse_fixture(include_str!("../tests/fixtures/pss-vect.txt"));
+        assert!(!examples.is_empty());
+        let em_bits = BigInt::from_bytes_be(&key["n"]).bitlen() - 1;
+        for example in examples {
+            let message = &example["message"];
+            let salt = example["salt"].clone();
+            let salt_len = salt.len();
+            let em = emsa_pss_encode_with_salt(message, em_bits, Sha1, salt);
+            assert_eq!(rsa_public(&key, &example["signature"]), em);
+            assert!(emsa_pss_verify(message, &em, em_bits, Sha1, salt_len));
+            assert!(!emsa_pss_verify(b"other", &em, em_bits, Sha1, salt_len));
+        }
+    }
+
+    #[test]
+    fn pss_rejects_oversized_salt() {
+        let hash = HashAlgorithm::Sha256;
+        assert!(emsa_pss_encode(b"abc", 1023, hash, usize::MAX).is_err());
+        assert!(emsa_pss_encode(b"abc", 1023, hash, usize::MAX - 31).is_err());
+        assert!(emsa_pss_encode(b"abc", 1023, hash, 95).is_err());
+        let em = emsa_pss_encode(b"abc", 1023, hash, 94).unwrap();
+        assert!(emsa_pss_verify(b"abc", &em, 1023, hash, 94));
+        assert!(!emsa_pss_verify(b"abc", &em, 1023, hash, usize::MAX));
+        assert!(!emsa_pss_verify(b"abc", &em, 1023, hash, usize::MAX - 33));
+    }
+
+    #[test]
+    fn pkcs1_v15_signature_from_openssl() {
+        let (key, examples) =
+            parse_fixture(include_str!("../tests/fixtures/pkcs1v15-sign-sha256.txt"));
+        let k = key["n"].len();
+        for example in examples {
+            let em = emsa_pkcs1_v15_encode(&example["message"], k, HashAlgorithm::Sha256).unwrap();
+            assert_eq!(rsa_public(&key, &example["signature"]), em);
+        }
+    }
+
+    #[test]
+    fn emsa_pkcs1_v15_digest_info() {
+        // RFC 8017 9.2 注 1 中的 DigestInfo 前缀
+        for (hash, prefix) in [
+            (
+                HashAlgorithm::Sha256,
+                "3031300d060960864801650304020105000420",
+            ),
+            (
+                HashAlgorithm::Sha384,
+                "3041300d060960864801650304020205000430",
+            ),
+            (
+                HashAlgorithm::Sha512,
+                "3051300d060960864801650304020305000440",
+            ),
+        ] {
+            let em = emsa_pkcs1_v15_encode(b"abc", 256, hash).unwrap();
+            let mut t = hex(prefix);
+            t.extend(hash.digest(b"abc"));
+            let ps_len = 256 - t.len() - 3;
+            assert_eq!(em[..2], [0x00, 0x01]);
+            assert!(em[2..2 + ps_len].iter().all(|b| *b == 0xff));
+            assert_eq!(em[2 + ps_len], 0x00);
+            assert_eq!(em[3 + ps_len..], t);
+        }
+    }
+
+    #[test]
+    fn mgf1_known_answers() {
+        assert_eq!(mgf1(Sha1, b"foo", 3), hex("1ac907"));
+        assert_eq!(mgf1(Sha1, b"foo", 5), hex("1ac9075cd4"));
+        assert_eq!(mgf1(Sha1, b"bar", 5), hex("bc0c655e01"));
+        assert_eq!(
+            mgf1(HashAlgorithm::Sha256, b"bar", 50),
+            hex("382576a7841021cc28fc4c0948753fb8312090cea942ea4c4e735d10dc724b155f9f6069f289d61daca0cb814502ef04eae1")
+        );
+    }
+
+    #[test]
+    fn pkcs1_v15_encryption_padding() {
+        let message = b"hello";
+        let em = pkcs1_v15_pad(message, 64).unwrap();
+        assert_eq!(em.len(), 64);
+        assert_eq!(em[..2], [0x00, 0x02]);
+        assert!(em[2..64 - message.len() - 1].iter().all(|b| *b != 0));
+        assert_eq!(pkcs1_v15_unpad(&em).unwrap(), message);
+
+        let mut bad = em.clone();
+        bad[1] = 0x01;
+        assert_eq!(pkcs1_v15_unpad(&bad), Err(DECRYPTION_ERROR));
+        // 填充串短于 8 字节
+        let mut short = vec![0x00, 0x02, 1, 1, 1, 1, 1, 1, 1, 0x00];
+        short.extend_from_slice(message);
+        assert_eq!(pkcs1_v15_unpad(&short), Err(DECRYPTION_ERROR));
+        assert_eq!(pkcs1_v15_pad(&[1; 54], 64), Err(Error::MessageTooLong));
+    }
+}
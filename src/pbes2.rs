@@ -0,0 +1,117 @@
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use rand::Rng;
+use sha2::Sha256;
+
+use crate::bigint::BigInt;
+use crate::der::{self, Reader, TAG_INTEGER};
+use crate::error::Error;
+
+/// 1.2.840.113549.1.5.13
+const PBES2_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d];
+/// 1.2.840.113549.1.5.12
+const PBKDF2_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0c];
+/// 1.2.840.113549.2.9
+const HMAC_SHA256_OID: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x02, 0x09];
+/// 2.16.840.1.101.3.4.1.42
+const AES256_CBC_OID: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+
+/// 导出时使用的 PBKDF2 迭代次数
+pub const ITERATIONS: u32 = 100_000;
+/// 导入时允许的最大迭代次数。迭代次数由输入决定，且在处理请求的线程上计算，不能太大
+const MAX_ITERATIONS: u64 = 1_000_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const IV_LEN: usize = 16;
+
+const DECRYPT_ERROR: Error = Error::Decryption("Incorrect passphrase or corrupted key");
+
+/// PBKDF2-HMAC-SHA256 派生 32 字节密钥
+pub fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+/// 用 PBES2 加密 `data`，输出 `EncryptedPrivateKeyInfo`
+pub fn encrypt(data: &[u8], passphrase: &str) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill(&mut salt);
+    let mut iv = [0u8; IV_LEN];
+    rng.fill(&mut iv);
+
+    let key = derive_key(passphrase, &salt, ITERATIONS);
+    let encrypted = cbc::Encryptor::<aes::Aes256>::new(&key.into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(data);
+
+    let kdf = der::sequence(&[
+        der::oid(PBKDF2_OID),
+        der::sequence(&[
+            der::octet_string(&salt),
+            der::integer(&BigInt::from_slice(&[ITERATIONS as u64])),
+            der::sequence(&[der::oid(HMAC_SHA256_OID), der::null()]),
+        ]),
+    ]);
+    let scheme = der::sequence(&[der::oid(AES256_CBC_OID), der::octet_string(&iv)]);
+    der::sequence(&[
+        der::sequence(&[der::oid(PBES2_OID), der::sequence(&[kdf, scheme])]),
+        der::octet_string(&encrypted),
+    ])
+}
+
+/// 解析 `EncryptedPrivateKeyInfo` 并解密，只支持 PBKDF2-HMAC-SHA256 与 AES-256-CBC
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::new(data);
+    let mut info = reader.read_sequence()?;
+    reader.finish()?;
+
+    let mut alg = info.read_sequence()?;
+    if alg.read_oid()? != PBES2_OID {
+        return Err(Error::Unsupported("Unsupported key encryption algorithm"));
+    }
+    let mut params = alg.read_sequence()?;
+    alg.finish()?;
+
+    let mut kdf = params.read_sequence()?;
+    if kdf.read_oid()? != PBKDF2_OID {
+        return Err(Error::Unsupported("Unsupported key derivation function"));
+    }
+    let mut kdf_params = kdf.read_sequence()?;
+    kdf.finish()?;
+    let salt = kdf_params.read_octet_string()?;
+    let iterations = kdf_params.read_integer()?.to_int().unwrap_or(u64::MAX);
+    if iterations == 0 || iterations > MAX_ITERATIONS {
+        return Err(Error::Unsupported("Unsupported PBKDF2 iteration count"));
+    }
+    // keyLength 可省略
+    if kdf_params.peek_tag() == Some(TAG_INTEGER)
+        && kdf_params.read_integer()?.to_int() != Ok(KEY_LEN as u64)
+    {
+        return Err(Error::Unsupported("Unsupported PBKDF2 key length"));
+    }
+    // prf 省略时默认为 HMAC-SHA1，不支持
+    let mut prf = kdf_params.read_sequence()?;
+    if prf.read_oid()? != HMAC_SHA256_OID {
+        return Err(Error::Unsupported("Unsupported PBKDF2 PRF"));
+    }
+    kdf_params.finish()?;
+
+    let mut scheme = params.read_sequence()?;
+    if scheme.read_oid()? != AES256_CBC_OID {
+        return Err(Error::Unsupported("Unsupported key encryption cipher"));
+    }
+    let iv = scheme.read_octet_string()?;
+    scheme.finish()?;
+    params.finish()?;
+    if iv.len() != IV_LEN {
+        return Err(Error::Parse("Invalid AES-CBC IV"));
+    }
+
+    let encrypted = info.read_octet_string()?;
+    info.finish()?;
+
+    let key = derive_key(passphrase, salt, iterations as u32);
+    cbc::Decryptor::<aes::Aes256>::new(&key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(encrypted)
+        .map_err(|_| DECRYPT_ERROR)
+}
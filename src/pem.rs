@@ -0,0 +1,41 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use crate::error::Error;
+
+/// 以 `-----BEGIN {label}-----` 包装 DER 数据，每行 64 个字符
+pub fn encode(label: &str, der: &[u8]) -> String {
+    let body = BASE64.encode(der);
+    let mut res = format!("-----BEGIN {label}-----\n");
+    for line in body.as_bytes().chunks(64) {
+        res.push_str(std::str::from_utf8(line).unwrap());
+        res.push('\n');
+    }
+    res.push_str(&format!("-----END {label}-----\n"));
+    res
+}
+
+pub fn is_pem(input: &str) -> bool {
+    input.trim_start().starts_with("-----BEGIN ")
+}
+
+/// 解析第一个 PEM 块，返回标签与 DER 数据
+pub fn decode(input: &str) -> Result<(&str, Vec<u8>), Error> {
+    let input = input.trim_start();
+    let rest = input
+        .strip_prefix("-----BEGIN ")
+        .ok_or(Error::Parse("Invalid PEM"))?;
+    let (label, rest) = rest
+        .split_once("-----")
+        .ok_or(Error::Parse("Invalid PEM"))?;
+    let (body, _) = rest
+        .split_once(&format!("-----END {label}-----"))
+        .ok_or(Error::Parse("Invalid PEM"))?;
+    let body = body
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>();
+    let der = BASE64
+        .decode(body)
+        .map_err(|_| Error::Parse("Invalid PEM"))?;
+    Ok((label, der))
+}
@@ -1,64 +1,402 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
 use lazy_static::lazy_static;
 
-use crate::algorithms;
-use crate::bigint::{mod_div, BigInt, ONE};
+use crate::algorithms::{self, ModPowBackend, ModPowContext, PrimalityEvent};
+use crate::bigint::{mod_div, BigInt, ONE, THREE};
+use crate::error::Error;
+use crate::hash::HashAlgorithm;
+use crate::padding;
 
-const E: u64 = 114493; // biggest prime smaller than 114514;
+/// 默认的公钥指数
+pub const DEFAULT_E: u64 = 65537;
+/// 允许生成和使用的模数位数
+pub const MIN_KEY_LEN: usize = 512;
+pub const MAX_KEY_LEN: usize = 8192;
+const KEY_LEN_STEP: usize = 8;
+/// 早期版本固定使用的公钥指数，用于兼容只给出 n 的公钥
+const LEGACY_E: u64 = 114493; // biggest prime smaller than 114514;
 
 lazy_static! {
-    static ref E_BIGINT: BigInt = BigInt::from_slice(&[E]);
+    static ref LEGACY_E_BIGINT: BigInt = BigInt::from_slice(&[LEGACY_E]);
+}
+
+/// 素数搜索中的事件，`candidate` 为候选数的序号，从 1 开始
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum KeyGenEvent {
+    /// 能被小素数 `divisor` 整除
+    SieveRejected { candidate: u64, divisor: u64 },
+    /// 在第 `round` 轮 Miller-Rabin 测试中被判定为合数
+    MillerRabinRejected { candidate: u64, round: usize },
+    /// 是素数，但 gcd(p - 1, e) != 1
+    ExponentRejected { candidate: u64 },
+    /// 找到了 `bits` 位的素数
+    PrimeFound { candidate: u64, bits: usize },
+}
+
+type Observer = Box<dyn Fn(KeyGenEvent) + Send + Sync>;
+
+/// 密钥生成的进度，可以在其他线程中读取或取消
+#[derive(Default)]
+pub struct KeyGenProgress {
+    candidates: AtomicU64,
+    mr_rounds: AtomicU64,
+    cancelled: AtomicBool,
+    observer: Option<Observer>,
+}
+
+impl KeyGenProgress {
+    /// 每个事件都会以生成密钥的线程调用 `observer`
+    pub fn with_observer(observer: impl Fn(KeyGenEvent) + Send + Sync + 'static) -> Self {
+        Self {
+            observer: Some(Box::new(observer)),
+            ..Default::default()
+        }
+    }
+
+    fn emit(&self, event: KeyGenEvent) {
+        if let Some(observer) = &self.observer {
+            observer(event);
+        }
+    }
+
+    /// 已尝试的素数候选个数
+    pub fn candidates(&self) -> u64 {
+        self.candidates.load(Ordering::Relaxed)
+    }
+
+    /// 已进行的 Miller-Rabin 测试轮数
+    pub fn mr_rounds(&self) -> u64 {
+        self.mr_rounds.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
 }
 
-fn gen_prime(bit_len: usize) -> BigInt {
-    let big_int_len = bit_len / BigInt::VALUE_LEN as usize;
+/// 生成恰好 `bit_len` 位的素数 p，保证 gcd(p - 1, e) = 1。
+/// 最高两位都为 1，因此两个这样的素数之积的位数恰好是两者位数之和
+fn gen_prime(bit_len: usize, e: &BigInt, progress: &KeyGenProgress) -> Result<BigInt, Error> {
+    const LIMB_BITS: usize = BigInt::VALUE_LEN as usize;
+    let big_int_len = bit_len.div_ceil(LIMB_BITS);
     loop {
+        if progress.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
         let mut num = BigInt::rand(big_int_len);
-        num.value[0] |= 1; // 确保不是偶数
+        // 去掉多余的高位，再置最高两位和最低位（确保不是偶数）
+        num.value[big_int_len - 1] &= BigInt::VALUE_MASK >> (big_int_len * LIMB_BITS - bit_len);
+        for bit in [bit_len - 1, bit_len - 2, 0] {
+            num.value[bit / LIMB_BITS] |= 1 << (bit % LIMB_BITS);
+        }
+        let candidate = progress.candidates.fetch_add(1, Ordering::Relaxed) + 1;
 
-        let (_, r) = mod_div(&num, &E_BIGINT);
-        if r.is_zero() {
+        let is_prime = algorithms::miller_rabin(&num, |event| {
+            match event {
+                PrimalityEvent::Round { .. } => {
+                    progress.mr_rounds.fetch_add(1, Ordering::Relaxed);
+                    return !progress.is_cancelled();
+                }
+                PrimalityEvent::SieveRejected { divisor } => {
+                    progress.emit(KeyGenEvent::SieveRejected { candidate, divisor })
+                }
+                PrimalityEvent::Rejected { round } => {
+                    progress.emit(KeyGenEvent::MillerRabinRejected { candidate, round })
+                }
+            }
+            true
+        });
+        if !is_prime {
             continue;
         }
-        if algorithms::miller_rabin(&num) {
-            return num;
+        if algorithms::gcd(&(&num - &ONE), e) != *ONE {
+            progress.emit(KeyGenEvent::ExponentRejected { candidate });
+            continue;
         }
+        progress.emit(KeyGenEvent::PrimeFound {
+            candidate,
+            bits: num.bitlen(),
+        });
+        return Ok(num);
     }
 }
 
-pub fn gen_keys(length: usize) -> (BigInt, BigInt) {
-    let pq_len = length / 2;
-    let p = gen_prime(pq_len);
-    let q = gen_prime(pq_len);
-    let n = &p * &q;
-    let phi_n = &(&p - &ONE) * &(&q - &ONE);
-    let barrett_m = phi_n.barrett_m();
+/// RSA 公钥
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RsaPublicKey {
+    pub n: BigInt,
+    pub e: BigInt,
+}
 
-    // 手动 gcd 一次，使得数变小到可以放入 u64
-    let (div, r) = mod_div(&phi_n, &E_BIGINT);
-    let (_, mut u, v) = algorithms::extended_euclid(E, r.to_int().unwrap(), &barrett_m, &phi_n);
-    let div_v = algorithms::barrett_mod(&(&v * &div), &barrett_m, &phi_n);
-    if u < div_v {
-        u = &u + &phi_n;
+impl RsaPublicKey {
+    pub fn new(n: BigInt, e: BigInt) -> Result<Self, Error> {
+        if n.value[0] & 1 == 0 || n <= *THREE {
+            return Err(Error::InvalidKey(
+                "Modulus must be an odd number greater than 3",
+            ));
+        }
+        // 过小的模数无法分块，过大的模数会让一次运算占用服务器很长时间
+        if !(MIN_KEY_LEN..=MAX_KEY_LEN).contains(&n.bitlen()) {
+            return Err(Error::InvalidKey(
+                "Modulus must be between 512 and 8192 bits",
+            ));
+        }
+        if e.value[0] & 1 == 0 || e < *THREE {
+            return Err(Error::InvalidKey(
+                "Public exponent must be an odd number no less than 3",
+            ));
+        }
+        if e >= n {
+            return Err(Error::InvalidKey(
+                "Public exponent must be smaller than modulus",
+            ));
+        }
+        Ok(Self { n, e })
+    }
+
+    /// 以逗号拼接的 `n,e`，省略 e 时视为早期版本生成的公钥
+    pub fn from_hex(hex: &str) -> Result<Self, Error> {
+        match hex.split_once(",") {
+            Some((n, e)) => Self::new(BigInt::from_hex(n)?, BigInt::from_hex(e)?),
+            None => Self::new(BigInt::from_hex(hex)?, LEGACY_E_BIGINT.clone()),
+        }
+    }
+
+    pub fn fmt_hex(&self) -> String {
+        format!("{},{}", self.n.fmt_hex(), self.e.fmt_hex())
+    }
+
+    fn ctx(&self) -> ModPowContext {
+        ModPowContext::new(&self.n, ModPowBackend::from_env())
     }
-    let d = algorithms::barrett_mod(&(&u - &div_v), &barrett_m, &phi_n);
-    (n, d)
 }
 
-pub fn str_to_bigints(input: &str, max_length: usize) -> Vec<BigInt> {
+/// RSA 私钥，带有 p、q 时私钥运算走 CRT
+#[derive(Clone, Debug)]
+pub struct RsaPrivateKey {
+    pub n: BigInt,
+    pub e: BigInt,
+    pub d: BigInt,
+    pub crt: Option<CrtParams>,
+}
+
+impl RsaPrivateKey {
+    pub fn new(n: BigInt, e: BigInt, d: BigInt) -> Result<Self, Error> {
+        let RsaPublicKey { n, e } = RsaPublicKey::new(n, e)?;
+        if d.is_zero() || d >= n {
+            return Err(Error::InvalidKey("Private exponent out of range"));
+        }
+        Ok(Self { n, e, d, crt: None })
+    }
+
+    pub fn from_primes(p: BigInt, q: BigInt, e: BigInt, d: BigInt) -> Result<Self, Error> {
+        Self::new(&p * &q, e, d)?.with_primes(&p, &q)
+    }
+
+    /// 附加 CRT 参数，p、q 必须是 n 的非平凡分解
+    pub fn with_primes(mut self, p: &BigInt, q: &BigInt) -> Result<Self, Error> {
+        self.crt = Some(CrtParams::new(&self.n, p, q, &self.d)?);
+        Ok(self)
+    }
+
+    /// 私钥以 `d` 或 `d,p,q,dp,dq,qinv` 的形式传输，n 和 e 取自对应的公钥
+    pub fn from_hex(public_key: RsaPublicKey, hex: &str) -> Result<Self, Error> {
+        let RsaPublicKey { n, e } = public_key;
+        let (d, crt) = match hex.split_once(",") {
+            Some((d, crt)) => (d, Some(crt)),
+            None => (hex, None),
+        };
+        let mut key = Self::new(n, e, BigInt::from_hex(d)?)?;
+        if let Some(crt) = crt {
+            key.crt = Some(CrtParams::from_hex(crt, &key.n, &key.d)?);
+        }
+        Ok(key)
+    }
+
+    pub fn fmt_hex(&self) -> String {
+        match &self.crt {
+            Some(crt) => format!("{},{}", self.d.fmt_hex(), crt.fmt_hex()),
+            None => self.d.fmt_hex(),
+        }
+    }
+
+    pub fn public_key(&self) -> RsaPublicKey {
+        RsaPublicKey {
+            n: self.n.clone(),
+            e: self.e.clone(),
+        }
+    }
+}
+
+/// 中国剩余定理加速私钥运算所需的参数
+#[derive(Clone, Debug)]
+pub struct CrtParams {
+    pub p: BigInt,
+    pub q: BigInt,
+    /// d mod (p - 1)
+    pub dp: BigInt,
+    /// d mod (q - 1)
+    pub dq: BigInt,
+    /// q^-1 mod p
+    pub qinv: BigInt,
+}
+
+impl CrtParams {
+    /// 检查 p、q 是 n 的非平凡分解，否则 p - 1 或 q - 1 可能为 0
+    pub fn new(n: &BigInt, p: &BigInt, q: &BigInt, d: &BigInt) -> Result<Self, Error> {
+        if p <= &*ONE || q <= &*ONE {
+            return Err(Error::InvalidKey("p and q must be greater than 1"));
+        }
+        if p == q {
+            return Err(Error::InvalidKey("p and q must be different primes"));
+        }
+        if &(p * q) != n {
+            return Err(Error::InvalidKey("p * q does not equal n"));
+        }
+        let (_, dp) = mod_div(d, &(p - &ONE));
+        let (_, dq) = mod_div(d, &(q - &ONE));
+        let qinv =
+            algorithms::mod_inverse(q, p).ok_or(Error::InvalidKey("p and q are not coprime"))?;
+        Ok(Self {
+            p: p.clone(),
+            q: q.clone(),
+            dp,
+            dq,
+            qinv,
+        })
+    }
+
+    /// 以逗号拼接的 `p,q,dp,dq,qinv`
+    pub fn fmt_hex(&self) -> String {
+        [&self.p, &self.q, &self.dp, &self.dq, &self.qinv]
+            .iter()
+            .map(|x| x.fmt_hex())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// 只使用其中的 p、q，`dp`、`dq`、`qinv` 由 `d` 重新计算，不信任传入的值
+    pub fn from_hex(hex: &str, n: &BigInt, d: &BigInt) -> Result<Self, Error> {
+        let parts = hex
+            .split(",")
+            .map(BigInt::from_hex)
+            .collect::<Result<Vec<_>, _>>()?;
+        let Ok([p, q, _, _, _]) = <[BigInt; 5]>::try_from(parts) else {
+            return Err(Error::Parse(
+                "CRT params should contain p, q, dp, dq and qinv",
+            ));
+        };
+        Self::new(n, &p, &q, d)
+    }
+}
+
+/// 私钥运算 c^d mod n，提供 CRT 参数时分别在模 p、q 下求幂再合并
+enum PrivateKeyOp<'a> {
+    Plain {
+        ctx: ModPowContext,
+        d: &'a BigInt,
+    },
+    Crt {
+        crt: &'a CrtParams,
+        p_ctx: ModPowContext,
+        q_ctx: ModPowContext,
+    },
+}
+
+impl<'a> PrivateKeyOp<'a> {
+    fn new(key: &'a RsaPrivateKey) -> Self {
+        let backend = ModPowBackend::from_env();
+        match &key.crt {
+            Some(crt) => Self::Crt {
+                crt,
+                p_ctx: ModPowContext::new(&crt.p, backend),
+                q_ctx: ModPowContext::new(&crt.q, backend),
+            },
+            None => Self::Plain {
+                ctx: ModPowContext::new(&key.n, backend),
+                d: &key.d,
+            },
+        }
+    }
+
+    fn power(&self, c: &BigInt) -> BigInt {
+        let (crt, p_ctx, q_ctx) = match self {
+            Self::Plain { ctx, d } => return ctx.mod_power(c, d),
+            Self::Crt { crt, p_ctx, q_ctx } => (crt, p_ctx, q_ctx),
+        };
+        let (_, c_p) = mod_div(c, &crt.p);
+        let (_, c_q) = mod_div(c, &crt.q);
+        let m1 = p_ctx.mod_power(&c_p, &crt.dp);
+        let m2 = q_ctx.mod_power(&c_q, &crt.dq);
+
+        // h = qinv * (m1 - m2) mod p, m = m2 + h * q
+        let (_, m2_p) = mod_div(&m2, &crt.p);
+        let diff = if m1 >= m2_p {
+            &m1 - &m2_p
+        } else {
+            &(&m1 + &crt.p) - &m2_p
+        };
+        let h = p_ctx.mod_mul(&crt.qinv, &diff);
+        &m2 + &(&h * &crt.q)
+    }
+}
+
+/// 检查要生成的密钥位数是否在允许的范围内
+pub fn check_key_len(length: usize) -> Result<(), Error> {
+    if !(MIN_KEY_LEN..=MAX_KEY_LEN).contains(&length) || !length.is_multiple_of(KEY_LEN_STEP) {
+        return Err(Error::InvalidInput(
+            "Key length must be a multiple of 8 between 512 and 8192",
+        ));
+    }
+    Ok(())
+}
+
+/// 以公钥指数 `e` 生成密钥，`e` 需要是不小于 3 的奇数。
+/// 通过 `progress` 报告进度，被取消时返回 `Error::Cancelled`
+pub fn gen_keys_with_progress(
+    length: usize,
+    e: &BigInt,
+    progress: &KeyGenProgress,
+) -> Result<RsaPrivateKey, Error> {
+    check_key_len(length)?;
+    if e.value[0] & 1 == 0 || e < &*THREE {
+        return Err(Error::InvalidKey(
+            "Public exponent must be an odd number no less than 3",
+        ));
+    }
+    // 位数为奇数时 p 多一位，n 恰好为 `length` 位
+    let p = gen_prime(length.div_ceil(2), e, progress)?;
+    let mut q = gen_prime(length / 2, e, progress)?;
+    while q == p {
+        q = gen_prime(length / 2, e, progress)?;
+    }
+
+    // d = e^-1 mod λ(n)，λ(n) = lcm(p - 1, q - 1)
+    let lambda_n = algorithms::lcm(&(&p - &ONE), &(&q - &ONE));
+    let d = algorithms::mod_inverse(e, &lambda_n)
+        .ok_or(Error::InvalidKey("Public exponent is not invertible"))?;
+    RsaPrivateKey::from_primes(p, q, e.clone(), d)
+}
+
+/// 每块 `max_length` 个 limb，limb 内为小端序
+fn bytes_to_bigints(input: &[u8], max_length: usize) -> Vec<BigInt> {
+    const LIMB_BYTES: usize = BigInt::VALUE_LEN as usize / 8;
     input
-        .to_owned()
-        .into_bytes()
-        .chunks(max_length * 4)
-        .map(|blk| blk.to_vec())
+        .chunks(max_length * LIMB_BYTES)
         .map(|block| {
             let value = block
-                .chunks(4)
-                .map(|blk| blk.to_vec())
-                .map(|four_u8s| {
-                    four_u8s
-                        .into_iter()
+                .chunks(LIMB_BYTES)
+                .map(|bytes| {
+                    bytes
+                        .iter()
                         .enumerate()
-                        .fold(0 as u64, |acc, (i, x)| acc + ((x as u64) << (i * 8)))
+                        .fold(0_u64, |acc, (i, x)| acc + ((*x as u64) << (i * 8)))
                 })
                 .collect::<Vec<_>>();
             let length = value.len();
@@ -67,77 +405,373 @@ pub fn str_to_bigints(input: &str, max_length: usize) -> Vec<BigInt> {
         .collect()
 }
 
-pub fn bigints_to_str(xs: Vec<BigInt>) -> String {
+pub fn str_to_bigints(input: &str, max_length: usize) -> Vec<BigInt> {
+    bytes_to_bigints(input.as_bytes(), max_length)
+}
+
+/// `bytes_to_bigints` 的逆过程，每块都补齐到 `max_length` 个 limb，块内的 0 不会丢失
+fn bigints_to_bytes(xs: Vec<BigInt>, max_length: usize) -> Result<Vec<u8>, Error> {
+    let mut res = vec![];
+    for x in xs {
+        if x.length > max_length {
+            return Err(Error::Padding("Invalid block encoding"));
+        }
+        let mut block = x.value[0..x.length]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<_>>();
+        block.resize(max_length * BigInt::VALUE_LEN as usize / 8, 0);
+        res.extend(block);
+    }
+    Ok(res)
+}
+
+pub fn bigints_to_str(xs: Vec<BigInt>) -> Result<String, Error> {
     let res = String::from_utf8(
         xs.into_iter()
             .map(|x| {
                 x.value[0..x.length]
                     .iter()
-                    .map(|v| {
-                        let mut res = vec![];
-                        for i in 1..=4 {
-                            // 取第 i 个 byte，并移到最低位
-                            let vv = (v & (((1 as u64) << (i * 8)) - 1)) >> (i - 1) * 8;
-                            res.push(vv as u8);
-                        }
-                        res
-                    })
+                    .flat_map(|v| v.to_le_bytes())
                     .collect::<Vec<_>>()
-                    .concat()
             })
             .collect::<Vec<_>>()
             .concat(),
     )
-    .expect("utf8 decode failed");
-    res.strip_suffix("\0").unwrap_or(&res).to_owned()
+    .map_err(|_| Error::Parse("Message is not valid UTF-8"))?;
+    Ok(res.trim_end_matches('\0').to_owned())
 }
 
-pub fn encrypt(input: &str, n: &BigInt, barrett_m: &BigInt) -> String {
-    str_to_bigints(input, n.length - 1)
-        .into_iter()
-        .map(|m| algorithms::mod_power(&m, &E_BIGINT, barrett_m, n).fmt_hex())
-        .collect::<Vec<_>>()
-        .join(",")
+/// 无填充时字节串末尾追加的结束标记，解码时去掉其后补齐的 0 即可恢复原长度
+const END_MARKER: u8 = 0x80;
+
+/// 加密时使用的填充方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Padding {
+    /// 不填充，对分块直接求幂（教科书 RSA）。末尾带结束标记，与早期版本的密文格式不同
+    None,
+    /// EME-PKCS1-v1_5
+    Pkcs1,
+    /// EME-OAEP，使用 SHA-256 和 MGF1
+    #[default]
+    Oaep,
 }
 
-pub fn decrypt(input: &str, n: &BigInt, barrett_m: &BigInt, d: &BigInt) -> String {
-    let ms = input
-        .split(",")
-        .into_iter()
-        .map(|s| {
-            let c = BigInt::from_hex(s).expect("Reading hex data failed");
-            algorithms::mod_power(&c, &d, &barrett_m, &n)
-        })
-        .collect();
-    bigints_to_str(ms)
+impl Padding {
+    const OAEP_HASH: HashAlgorithm = HashAlgorithm::Sha256;
+
+    /// 每个分块最多能容纳的明文字节数
+    fn max_message_len(&self, k: usize) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Pkcs1 => k.saturating_sub(11),
+            Self::Oaep => k.saturating_sub(2 * Self::OAEP_HASH.output_len() + 2),
+        }
+    }
+
+    /// `label` 只在 OAEP 中使用
+    fn pad(&self, message: &[u8], k: usize, label: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::None => Ok(message.to_vec()),
+            Self::Pkcs1 => padding::pkcs1_v15_pad(message, k),
+            Self::Oaep => padding::oaep_pad(message, k, Self::OAEP_HASH, label),
+        }
+    }
+
+    fn unpad(&self, em: &[u8], label: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::None => Ok(em.to_vec()),
+            Self::Pkcs1 => padding::pkcs1_v15_unpad(em),
+            Self::Oaep => padding::oaep_unpad(em, Self::OAEP_HASH, label),
+        }
+    }
 }
 
-pub fn sign(input: &str, n: &BigInt, barrett_m: &BigInt, d: &BigInt) -> String {
-    str_to_bigints(input, n.length - 1)
-        .into_iter()
-        .map(|m| algorithms::mod_power(&m, d, barrett_m, n).fmt_hex())
-        .collect::<Vec<_>>()
-        .join(",")
+/// 长度为 `message_len` 的明文加密后的分块数
+pub fn block_count(
+    message_len: usize,
+    key: &RsaPublicKey,
+    padding: Padding,
+) -> Result<usize, Error> {
+    if padding == Padding::None {
+        // 末尾的结束标记占一个字节
+        let block_len = (key.n.length - 1) * BigInt::VALUE_LEN as usize / 8;
+        return Ok((message_len + 1).div_ceil(block_len));
+    }
+    let block_len = padding.max_message_len(key.n.bytelen());
+    if block_len == 0 {
+        return Err(Error::InvalidKey("Modulus too small for padding"));
+    }
+    Ok(message_len.div_ceil(block_len).max(1))
 }
 
-pub fn ver_sign(message: &str, input: &str, n: &BigInt, barrett_m: &BigInt) -> (bool, String) {
-    let ms: Vec<BigInt> = input
-        .split(",")
+/// 加密任意字节串，解密后长度与内容完全一致
+pub fn encrypt_bytes(
+    input: &[u8],
+    key: &RsaPublicKey,
+    padding: Padding,
+    label: &[u8],
+) -> Result<String, Error> {
+    let ctx = key.ctx();
+    if padding == Padding::None {
+        let mut bytes = input.to_vec();
+        bytes.push(END_MARKER);
+        return Ok(bytes_to_bigints(&bytes, key.n.length - 1)
+            .into_iter()
+            .map(|m| ctx.mod_power(&m, &key.e).fmt_hex())
+            .collect::<Vec<_>>()
+            .join(","));
+    }
+
+    let k = key.n.bytelen();
+    let block_len = padding.max_message_len(k);
+    if block_len == 0 {
+        return Err(Error::InvalidKey("Modulus too small for padding"));
+    }
+    // 空消息也需要一个分块
+    let blocks = if input.is_empty() {
+        vec![input]
+    } else {
+        input.chunks(block_len).collect()
+    };
+    Ok(blocks
         .into_iter()
-        .map(|s| {
-            let c = BigInt::from_hex(s).expect("Reading hex data failed");
-            algorithms::mod_power(&c, &E_BIGINT, &barrett_m, &n)
+        .map(|block| {
+            let em = padding.pad(block, k, label)?;
+            Ok(ctx.mod_power(&BigInt::from_bytes_be(&em), &key.e).fmt_hex())
         })
-        .collect();
-    let m = bigints_to_str(ms);
-    for ch in message.as_bytes() {
-        print!("{} ", ch);
-    }
-    println!();
-    for ch in m.as_bytes() {
-        print!("{} ", ch);
-    }
-    println!();
-    println!("message = {}, m = {}", message, m);
-    (m.trim_end_matches("\0") == message, m)
+        .collect::<Result<Vec<_>, Error>>()?
+        .join(","))
+}
+
+/// `encrypt_bytes` 的逆过程
+pub fn decrypt_bytes(
+    input: &str,
+    key: &RsaPrivateKey,
+    padding: Padding,
+    label: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let op = PrivateKeyOp::new(key);
+    if padding == Padding::None {
+        let ms = input
+            .split(",")
+            .map(|s| {
+                let c = BigInt::from_hex(s)?;
+                if c >= key.n {
+                    return Err(padding::DECRYPTION_ERROR);
+                }
+                Ok(op.power(&c))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let mut bytes = bigints_to_bytes(ms, key.n.length - 1)?;
+        while bytes.last() == Some(&0) {
+            bytes.pop();
+        }
+        if bytes.pop() != Some(END_MARKER) {
+            return Err(Error::Padding("Invalid block encoding"));
+        }
+        return Ok(bytes);
+    }
+
+    let k = key.n.bytelen();
+    let mut message = vec![];
+    for s in input.split(",") {
+        let c = BigInt::from_hex(s)?;
+        if c >= key.n {
+            return Err(padding::DECRYPTION_ERROR);
+        }
+        let em = op.power(&c).to_bytes_be(k);
+        message.extend(padding.unpad(&em, label)?);
+    }
+    Ok(message)
+}
+
+/// 签名方案
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureScheme {
+    /// 不做哈希，对消息分块直接求幂（教科书 RSA），不安全
+    None,
+    /// RSASSA-PKCS1-v1_5
+    #[default]
+    Pkcs1,
+    /// RSASSA-PSS，使用 MGF1，掩码与消息使用同一哈希算法
+    Pss,
+}
+
+/// PSS 的盐长度，省略时与摘要等长，但不超过模数 `n` 能容纳的长度
+fn pss_salt_len(n: &BigInt, hash: HashAlgorithm, salt_len: Option<usize>) -> Result<usize, Error> {
+    let em_len = (n.bitlen() - 1).div_ceil(8);
+    let max_len = em_len
+        .checked_sub(hash.output_len() + 2)
+        .ok_or(Error::InvalidKey("Modulus too small for hash"))?;
+    match salt_len {
+        None => Ok(hash.output_len().min(max_len)),
+        Some(len) if len > max_len => Err(Error::InvalidInput("Salt too long for key")),
+        Some(len) => Ok(len),
+    }
+}
+
+/// `hash` 只在带填充的方案中使用，`salt_len` 只在 PSS 中使用，
+/// 默认与摘要等长，密钥较短时取能容纳的最大长度
+pub fn sign(
+    input: &str,
+    key: &RsaPrivateKey,
+    scheme: SignatureScheme,
+    hash: HashAlgorithm,
+    salt_len: Option<usize>,
+) -> Result<String, Error> {
+    let op = PrivateKeyOp::new(key);
+    let em = match scheme {
+        SignatureScheme::None => {
+            return Ok(str_to_bigints(input, key.n.length - 1)
+                .into_iter()
+                .map(|m| op.power(&m).fmt_hex())
+                .collect::<Vec<_>>()
+                .join(","))
+        }
+        SignatureScheme::Pkcs1 => {
+            padding::emsa_pkcs1_v15_encode(input.as_bytes(), key.n.bytelen(), hash)?
+        }
+        SignatureScheme::Pss => padding::emsa_pss_encode(
+            input.as_bytes(),
+            key.n.bitlen() - 1,
+            hash,
+            pss_salt_len(&key.n, hash, salt_len)?,
+        )?,
+    };
+    Ok(op.power(&BigInt::from_bytes_be(&em)).fmt_hex())
+}
+
+pub fn ver_sign(
+    message: &str,
+    input: &str,
+    key: &RsaPublicKey,
+    scheme: SignatureScheme,
+    hash: HashAlgorithm,
+    salt_len: Option<usize>,
+) -> Result<bool, Error> {
+    let ctx = key.ctx();
+    // 签名值必须小于 n，否则直接视为无效
+    let recover = || -> Result<Option<BigInt>, Error> {
+        let s = BigInt::from_hex(input)?;
+        Ok((s < key.n).then(|| ctx.mod_power(&s, &key.e)))
+    };
+    match scheme {
+        SignatureScheme::None => {
+            let ms = input
+                .split(",")
+                .map(BigInt::from_hex)
+                .collect::<Result<Vec<_>, Error>>()?;
+            if ms.iter().any(|s| *s >= key.n) {
+                return Ok(false);
+            }
+            let ms = ms.iter().map(|s| ctx.mod_power(s, &key.e)).collect();
+            Ok(bigints_to_str(ms).is_ok_and(|m| m == message))
+        }
+        SignatureScheme::Pkcs1 => {
+            let Some(m) = recover()? else {
+                return Ok(false);
+            };
+            let k = key.n.bytelen();
+            let expected = padding::emsa_pkcs1_v15_encode(message.as_bytes(), k, hash)?;
+            Ok(m.to_bytes_be(k) == expected)
+        }
+        SignatureScheme::Pss => {
+            let Some(m) = recover()? else {
+                return Ok(false);
+            };
+            let em_bits = key.n.bitlen() - 1;
+            Ok(padding::emsa_pss_verify(
+                message.as_bytes(),
+                &m.to_bytes_be(em_bits.div_ceil(8)),
+                em_bits,
+                hash,
+                pss_salt_len(&key.n, hash, salt_len)?,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> RsaPrivateKey {
+        let e = BigInt::from_slice(&[DEFAULT_E]);
+        gen_keys_with_progress(MIN_KEY_LEN, &e, &KeyGenProgress::default()).unwrap()
+    }
+
+    #[test]
+    fn crt_rejects_trivial_factors() {
+        let key = test_key();
+        let crt = key.crt.clone().unwrap();
+        let d = key.d.clone();
+        // p = 1, q = n 时 p - 1 为 0，不能走到求模
+        for (p, q) in [
+            (ONE.clone(), key.n.clone()),
+            (key.n.clone(), ONE.clone()),
+            (BigInt::from_slice(&[0]), key.n.clone()),
+            (crt.p.clone(), crt.p.clone()),
+            (crt.p.clone(), &crt.q + &ONE),
+        ] {
+            assert!(key.clone().with_primes(&p, &q).is_err());
+            let hex = format!("{},{},{},1,1,1", d.fmt_hex(), p.fmt_hex(), q.fmt_hex());
+            assert!(RsaPrivateKey::from_hex(key.public_key(), &hex).is_err());
+        }
+        assert!(RsaPrivateKey::from_primes(ONE.clone(), key.n.clone(), key.e.clone(), d).is_err());
+        assert!(key.clone().with_primes(&crt.q, &crt.p).is_ok());
+    }
+
+    #[test]
+    fn pss_salt_len_fits_key() {
+        let key = test_key();
+        let public_key = key.public_key();
+        for hash in [HashAlgorithm::Sha256, HashAlgorithm::Sha384] {
+            let signature = sign("abc", &key, SignatureScheme::Pss, hash, None).unwrap();
+            assert!(ver_sign(
+                "abc",
+                &signature,
+                &public_key,
+                SignatureScheme::Pss,
+                hash,
+                None
+            )
+            .unwrap());
+        }
+        // 512 位密钥放不下 SHA-512 的摘要
+        assert_eq!(
+            sign(
+                "abc",
+                &key,
+                SignatureScheme::Pss,
+                HashAlgorithm::Sha512,
+                None
+            )
+            .unwrap_err(),
+            Error::InvalidKey("Modulus too small for hash")
+        );
+        let hash = HashAlgorithm::Sha256;
+        assert_eq!(pss_salt_len(&key.n, hash, None).unwrap(), 30);
+        assert_eq!(pss_salt_len(&key.n, hash, Some(30)).unwrap(), 30);
+        for salt_len in [31, usize::MAX] {
+            assert_eq!(
+                sign("abc", &key, SignatureScheme::Pss, hash, Some(salt_len)).unwrap_err(),
+                Error::InvalidInput("Salt too long for key")
+            );
+        }
+    }
+
+    #[test]
+    fn jwk_rejects_trivial_factors() {
+        let key = test_key();
+        let mut jwk = key.to_jwk();
+        jwk.p = Some("AQ".to_owned());
+        jwk.q = Some(jwk.n.clone());
+        assert_eq!(
+            RsaPrivateKey::from_jwk(&jwk).unwrap_err(),
+            Error::InvalidKey("p and q must be greater than 1")
+        );
+    }
 }